@@ -96,7 +96,7 @@ impl Visitor for Validator {
         Skip(!self.game.success)
     }
 
-    fn begin_variation(&mut self) -> Skip {
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
         Skip(true) // stay in the mainline
     }
 
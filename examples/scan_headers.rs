@@ -0,0 +1,75 @@
+// Compares BufferedReader::scan_headers() with a visitor that collects the
+// same headers into a single arena and skips the movetext.
+// Usage: cargo run --release --features compression --example scan_headers -- [PGN]...
+
+use std::{env, io, ops::Range, time::Instant};
+
+use pgn_reader::{BufferedReader, HeaderTable, RawHeader, Skip, Visitor};
+
+#[derive(Default)]
+struct Headers {
+    arena: Vec<u8>,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+    games: Vec<Range<usize>>,
+    start: usize,
+}
+
+impl Headers {
+    fn new() -> Headers {
+        Headers::default()
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> Range<usize> {
+        let start = self.arena.len();
+        self.arena.extend_from_slice(bytes);
+        start..self.arena.len()
+    }
+}
+
+impl Visitor for Headers {
+    type Result = ();
+
+    fn begin_game(&mut self) {
+        self.start = self.headers.len();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        let key = self.push(key);
+        let value = self.push(value.as_bytes());
+        self.headers.push((key, value));
+    }
+
+    fn end_headers(&mut self) -> Skip {
+        Skip(true)
+    }
+
+    fn end_game(&mut self) {
+        self.games.push(self.start..self.headers.len());
+    }
+}
+
+fn main() -> Result<(), io::Error> {
+    for arg in env::args().skip(1) {
+        let start = Instant::now();
+        let mut table = HeaderTable::new();
+        let games = BufferedReader::open_auto(&arg)?.scan_headers(&mut table)?;
+        println!(
+            "{}: scan_headers: {} games in {:?}",
+            arg,
+            games,
+            start.elapsed()
+        );
+
+        let start = Instant::now();
+        let mut headers = Headers::new();
+        BufferedReader::open_auto(&arg)?.read_all(&mut headers)?;
+        println!(
+            "{}: visitor: {} games in {:?}",
+            arg,
+            headers.games.len(),
+            start.elapsed()
+        );
+    }
+
+    Ok(())
+}
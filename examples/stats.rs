@@ -1,7 +1,7 @@
 // Counts games, moves and other tokens in PGNs.
-// Usage: cargo run --release --example stats -- [PGN]...
+// Usage: cargo run --release --features compression --example stats -- [PGN]...
 
-use std::{env, fs::File, io};
+use std::{env, io};
 
 use pgn_reader::{BufferedReader, Nag, Outcome, RawComment, RawHeader, SanPlus, Visitor};
 
@@ -56,21 +56,7 @@ impl Visitor for Stats {
 
 fn main() -> Result<(), io::Error> {
     for arg in env::args().skip(1) {
-        let file = File::open(&arg).expect("fopen");
-
-        let uncompressed: Box<dyn io::Read> = if arg.ends_with(".bz2") {
-            Box::new(bzip2::read::MultiBzDecoder::new(file))
-        } else if arg.ends_with(".xz") {
-            Box::new(xz2::read::XzDecoder::new(file))
-        } else if arg.ends_with(".gz") {
-            Box::new(flate2::read::GzDecoder::new(file))
-        } else if arg.ends_with(".lz4") {
-            Box::new(lz4::Decoder::new(file)?)
-        } else {
-            Box::new(file)
-        };
-
-        let mut reader = BufferedReader::new(uncompressed);
+        let mut reader = BufferedReader::open_auto(&arg)?;
 
         let mut stats = Stats::new();
         reader.read_all(&mut stats)?;
@@ -1,34 +1,31 @@
 // Validates moves in PGNs.
 // Usage: cargo run --release --example validate -- [PGN]...
 
-use std::{env, fs::File, io};
+use std::{env, io};
 
-use pgn_reader::{BufferedReader, RawHeader, SanPlus, Skip, Visitor};
+use pgn_reader::{MultiReader, RawHeader, SanPlus, Skip, Visitor};
 use shakmaty::{fen::Fen, CastlingMode, Chess, Position};
 
 struct Validator {
-    games: usize,
     pos: Chess,
-    success: bool,
+    error: Option<String>,
 }
 
 impl Validator {
     fn new() -> Validator {
         Validator {
-            games: 0,
             pos: Chess::default(),
-            success: true,
+            error: None,
         }
     }
 }
 
 impl Visitor for Validator {
-    type Result = bool;
+    type Result = Option<String>;
 
     fn begin_game(&mut self) {
-        self.games += 1;
         self.pos = Chess::default();
-        self.success = true;
+        self.error = None;
     }
 
     fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
@@ -37,11 +34,7 @@ impl Visitor for Validator {
             let fen = match Fen::from_ascii(value.as_bytes()) {
                 Ok(fen) => fen,
                 Err(err) => {
-                    eprintln!(
-                        "invalid fen header in game {}: {} ({:?})",
-                        self.games, err, value
-                    );
-                    self.success = false;
+                    self.error = Some(format!("invalid fen header: {} ({:?})", err, value));
                     return;
                 }
             };
@@ -49,11 +42,7 @@ impl Visitor for Validator {
             self.pos = match fen.into_position(CastlingMode::Chess960) {
                 Ok(pos) => pos,
                 Err(err) => {
-                    eprintln!(
-                        "illegal fen header in game {}: {} ({:?})",
-                        self.games, err, value
-                    );
-                    self.success = false;
+                    self.error = Some(format!("illegal fen header: {} ({:?})", err, value));
                     return;
                 }
             };
@@ -61,45 +50,46 @@ impl Visitor for Validator {
     }
 
     fn end_headers(&mut self) -> Skip {
-        Skip(!self.success)
+        Skip(self.error.is_some())
     }
 
-    fn begin_variation(&mut self) -> Skip {
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
         Skip(true) // stay in the mainline
     }
 
     fn san(&mut self, san_plus: SanPlus) {
-        if self.success {
+        if self.error.is_none() {
             match san_plus.san.to_move(&self.pos) {
                 Ok(m) => self.pos.play_unchecked(&m),
-                Err(err) => {
-                    eprintln!("error in game {}: {} {}", self.games, err, san_plus);
-                    self.success = false;
-                }
+                Err(err) => self.error = Some(format!("{} {}", err, san_plus)),
             }
         }
     }
 
     fn end_game(&mut self) -> Self::Result {
-        self.success
+        self.error.take()
     }
 }
 
 fn main() -> io::Result<()> {
     let mut success = true;
 
-    for arg in env::args().skip(1) {
-        let file = File::open(&arg)?;
-        let mut reader = BufferedReader::new(file);
-
-        let mut validator = Validator::new();
-        while let Some(ok) = reader.read_game(&mut validator)? {
-            success &= ok;
+    let mut reader = MultiReader::open(env::args().skip(1));
+    let mut validator = Validator::new();
+    while let Some(error) = reader.read_game(&mut validator)? {
+        if let Some(error) = error {
+            eprintln!(
+                "{}:{}: {}",
+                reader.source_name().unwrap_or_default(),
+                reader.game_line(),
+                error
+            );
+            success = false;
         }
-
-        println!("{}: {}", arg, if success { "success" } else { "errors" });
     }
 
+    println!("{}", if success { "success" } else { "errors" });
+
     if !success {
         ::std::process::exit(1);
     }
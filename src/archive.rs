@@ -0,0 +1,261 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(feature = "tar")]
+use std::io::Read;
+#[cfg(feature = "zip")]
+use std::io::Seek;
+use std::{fmt, io, path::Path};
+
+use crate::{reader::BufferedReader, visitor::Visitor};
+
+fn is_pgn(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pgn"))
+}
+
+/// Reads the games of all `*.pgn` entries in a zip archive, in the order
+/// they appear in the central directory.
+///
+/// [`Visitor::begin_source()`] is called with the name of each entry before
+/// its games.
+///
+/// ```
+/// # use std::io;
+/// # fn try_main() -> io::Result<()> {
+/// use std::fs::File;
+/// use pgn_reader::ZipReader;
+/// # struct MyVisitor;
+/// # impl pgn_reader::Visitor for MyVisitor {
+/// #     type Result = ();
+/// #     fn end_game(&mut self) {}
+/// # }
+///
+/// let mut reader = ZipReader::new(File::open("twic1500g.zip")?)?;
+/// reader.read_all(&mut MyVisitor)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Visitor::begin_source()`]: trait.Visitor.html#method.begin_source
+#[cfg(feature = "zip")]
+pub struct ZipReader<R> {
+    archive: zip::ZipArchive<R>,
+}
+
+#[cfg(feature = "zip")]
+impl<R: io::Read + Seek> ZipReader<R> {
+    /// Create a new reader for a zip archive.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * The central directory of the archive is invalid.
+    pub fn new(inner: R) -> io::Result<ZipReader<R>> {
+        Ok(ZipReader {
+            archive: zip::ZipArchive::new(inner)?,
+        })
+    }
+
+    /// Returns the names of the `*.pgn` entries that will be read.
+    pub fn pgn_names(&self) -> impl Iterator<Item = &str> {
+        self.archive.file_names().filter(|name| is_pgn(name))
+    }
+
+    /// Read all games of all `*.pgn` entries.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Invalid or unsupported zip entries.
+    /// * Irrecoverable parser errors.
+    pub fn read_all<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
+        for index in 0..self.archive.len() {
+            let entry = self.archive.by_index(index)?;
+            if !entry.is_file() || !is_pgn(entry.name()) {
+                continue;
+            }
+
+            visitor.begin_source(entry.name());
+            BufferedReader::new(entry).read_all(visitor)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<R> fmt::Debug for ZipReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZipReader").finish_non_exhaustive()
+    }
+}
+
+/// Reads the games of all `*.pgn` entries in a tar archive, in the order
+/// they appear in the archive.
+///
+/// The archive is read in a single pass, so it can be streamed, for example
+/// from a decompressor.
+///
+/// [`Visitor::begin_source()`] is called with the path of each entry before
+/// its games.
+///
+/// [`Visitor::begin_source()`]: trait.Visitor.html#method.begin_source
+#[cfg(feature = "tar")]
+pub struct TarReader<R: Read> {
+    archive: tar::Archive<R>,
+}
+
+#[cfg(feature = "tar")]
+impl<R: Read> TarReader<R> {
+    /// Create a new reader for an uncompressed tar archive.
+    pub fn new(inner: R) -> TarReader<R> {
+        TarReader {
+            archive: tar::Archive::new(inner),
+        }
+    }
+
+    /// Read all games of all `*.pgn` entries.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Invalid tar headers.
+    /// * Irrecoverable parser errors.
+    pub fn read_all<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
+        for entry in self.archive.entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if !is_pgn(&name) {
+                continue;
+            }
+
+            visitor.begin_source(&name);
+            BufferedReader::new(entry).read_all(visitor)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(
+    feature = "tar",
+    any(
+        feature = "bzip2",
+        feature = "xz",
+        feature = "gzip",
+        feature = "lz4",
+        feature = "zstd"
+    )
+))]
+impl TarReader<Box<dyn Read + Send>> {
+    /// Open a tar archive, like a `.tar.zst` bundle, that is transparently
+    /// decompressed if required.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error when opening or reading the file.
+    /// * The file is compressed with a format whose crate feature is not
+    ///   enabled.
+    pub fn open_auto<P: AsRef<Path>>(path: P) -> io::Result<TarReader<Box<dyn Read + Send>>> {
+        crate::compression::decompress_auto(std::fs::File::open(path)?).map(TarReader::new)
+    }
+}
+
+#[cfg(feature = "tar")]
+impl<R: Read> fmt::Debug for TarReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TarReader").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct SourceCollector {
+        games: Vec<String>,
+        source: String,
+    }
+
+    impl Visitor for SourceCollector {
+        type Result = ();
+
+        fn begin_source(&mut self, name: &str) {
+            self.source = name.to_owned();
+        }
+
+        fn end_game(&mut self) {
+            self.games.push(self.source.clone());
+        }
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip() -> io::Result<()> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("a.pgn", options)?;
+        writer.write_all(b"1. e4 *\n\n1. d4 *")?;
+        writer.start_file("readme.txt", options)?;
+        writer.write_all(b"1. c4 *")?;
+        writer.start_file("b.PGN", options)?;
+        writer.write_all(b"1. f4 *")?;
+        let archive = writer.finish()?;
+
+        let mut collector = SourceCollector::default();
+        let mut reader = ZipReader::new(archive)?;
+        assert_eq!(reader.pgn_names().collect::<Vec<_>>(), ["a.pgn", "b.PGN"]);
+        reader.read_all(&mut collector)?;
+        assert_eq!(collector.games, ["a.pgn", "a.pgn", "b.PGN"]);
+        Ok(())
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_tar() -> io::Result<()> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in [
+            ("2024/01.pgn", &b"1. e4 *"[..]),
+            ("2024/notes.txt", &b"1. c4 *"[..]),
+            ("2024/02.pgn", &b"1. d4 *\n\n1. f4 *\n"[..]),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, path, data)?;
+        }
+        let archive = builder.into_inner()?;
+
+        let mut collector = SourceCollector::default();
+        TarReader::new(Cursor::new(archive)).read_all(&mut collector)?;
+        assert_eq!(
+            collector.games,
+            ["2024/01.pgn", "2024/02.pgn", "2024/02.pgn"]
+        );
+        Ok(())
+    }
+}
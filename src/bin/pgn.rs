@@ -0,0 +1,764 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Everyday operations on PGN files.
+//!
+//! Inputs may be compressed, and `-` or no inputs at all read from stdin.
+//! Games are written to stdout, everything else as one JSON object per line.
+//!
+//! Exit status is 0 on success, 1 if `validate` found errors, and 2 on
+//! usage or I/O errors.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    process,
+};
+
+use pgn_reader::{
+    BufferedReader, Dedup, Filter, HeaderIndex, Headers, Nag, Outcome, OutcomeStats, RawComment,
+    RawHeader, SanPlus, Skip, Visitor,
+};
+use serde_json::json;
+use shakmaty::{fen::Fen, uci::UciMove, CastlingMode, Chess, Color, Move, Position};
+
+const USAGE: &str = "usage: pgn <command> [options] [FILE]...
+
+Commands:
+  stats                        count games, moves and annotations
+  validate                     check that all mainline moves are legal
+  filter EXPR                  keep games whose headers match EXPR
+  split [-n GAMES] [-o PREFIX] write chunks of GAMES games (default 1000)
+  merge                        concatenate games of all inputs
+  dedup [--report]             drop duplicate games, or report clusters
+  convert [--format pgn|json|uci]
+                               rewrite games in export format, as JSON
+                               lines or as UCI moves
+  index [-o INDEX] FILE        create or update a header index
+  head [-n GAMES]              keep the first GAMES games (default 10)
+
+Inputs may be compressed. Reads stdin if no FILE is given or FILE is -.";
+
+type Reader = BufferedReader<Box<dyn Read + Send>>;
+
+fn open(input: &str) -> io::Result<Reader> {
+    if input == "-" {
+        BufferedReader::from_reader_auto(io::stdin())
+    } else {
+        BufferedReader::open_auto(input)
+    }
+}
+
+struct Args {
+    values: HashMap<&'static str, String>,
+    switches: HashSet<&'static str>,
+    inputs: Vec<String>,
+}
+
+impl Args {
+    fn parse<I>(
+        args: I,
+        options: &[&'static str],
+        switches: &[&'static str],
+    ) -> Result<Args, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut parsed = Args {
+            values: HashMap::new(),
+            switches: HashSet::new(),
+            inputs: Vec::new(),
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.inputs.extend(args);
+                break;
+            } else if let Some(&option) = options.iter().find(|&&option| option == arg) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", option))?;
+                parsed.values.insert(option, value);
+            } else if let Some(&switch) = switches.iter().find(|&&switch| switch == arg) {
+                parsed.switches.insert(switch);
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(format!("unknown option {}", arg));
+            } else {
+                parsed.inputs.push(arg);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn number(&self, option: &str, default: u64) -> Result<u64, String> {
+        match self.values.get(option) {
+            Some(value) => value
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("invalid value for {}: {}", option, value)),
+            None => Ok(default),
+        }
+    }
+
+    fn inputs(&self) -> Vec<&str> {
+        if self.inputs.is_empty() {
+            vec!["-"]
+        } else {
+            self.inputs.iter().map(String::as_str).collect()
+        }
+    }
+}
+
+/// Copies games to the output, separated by empty lines.
+struct GameWriter<W: Write> {
+    out: W,
+    games: u64,
+}
+
+impl<W: Write> GameWriter<W> {
+    fn new(out: W) -> GameWriter<W> {
+        GameWriter { out, games: 0 }
+    }
+
+    fn write(&mut self, game: &[u8]) -> io::Result<()> {
+        if self.games > 0 {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.write_all(game)?;
+        if !game.ends_with(b"\n") {
+            self.out.write_all(b"\n")?;
+        }
+        self.games += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<u64> {
+        self.out.flush()?;
+        Ok(self.games)
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    games: u64,
+    headers: u64,
+    moves: u64,
+    nags: u64,
+    comments: u64,
+    variations: u64,
+    outcomes: OutcomeStats,
+}
+
+impl Visitor for Stats {
+    type Result = ();
+
+    fn header(&mut self, _key: &[u8], _value: RawHeader<'_>) {
+        self.headers += 1;
+    }
+
+    fn san(&mut self, _san_plus: SanPlus) {
+        self.moves += 1;
+    }
+
+    fn nag(&mut self, _nag: Nag) {
+        self.nags += 1;
+    }
+
+    fn comment(&mut self, _comment: RawComment<'_>) {
+        self.comments += 1;
+    }
+
+    fn end_variation(&mut self) {
+        self.variations += 1;
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.outcomes.add(outcome);
+    }
+
+    fn end_game(&mut self) {
+        self.games += 1;
+    }
+}
+
+/// Replays the mainline, for validation and UCI output.
+struct Mainline {
+    pos: Chess,
+    moves: Vec<Move>,
+    error: Option<String>,
+}
+
+impl Mainline {
+    fn new() -> Mainline {
+        Mainline {
+            pos: Chess::default(),
+            moves: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl Visitor for Mainline {
+    type Result = Result<Vec<Move>, String>;
+
+    fn begin_game(&mut self) {
+        self.pos = Chess::default();
+        self.moves.clear();
+        self.error = None;
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        if key == b"FEN" {
+            match Fen::from_ascii(value.as_bytes()) {
+                Ok(fen) => match fen.into_position(CastlingMode::Chess960) {
+                    Ok(pos) => self.pos = pos,
+                    Err(err) => self.error = Some(format!("illegal fen header: {}", err)),
+                },
+                Err(err) => self.error = Some(format!("invalid fen header: {}", err)),
+            }
+        }
+    }
+
+    fn end_headers(&mut self) -> Skip {
+        Skip(self.error.is_some())
+    }
+
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+        Skip(true)
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        if self.error.is_none() {
+            match san_plus.san.to_move(&self.pos) {
+                Ok(m) => {
+                    self.moves.push(m);
+                    self.pos.play_unchecked(m);
+                }
+                Err(err) => {
+                    self.error = Some(format!(
+                        "{} {} at ply {}",
+                        err,
+                        san_plus,
+                        self.moves.len() + 1
+                    ))
+                }
+            }
+        }
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(self.moves.split_off(0)),
+        }
+    }
+}
+
+/// Rewrites games in export format: normalized move numbers, spacing and
+/// line breaks, keeping comments, NAGs and variations.
+struct Exporter {
+    out: Vec<u8>,
+    line: usize,
+    headers: bool,
+    turn: Color,
+    fullmoves: u32,
+    number: bool,
+    open: usize,
+    outcome: Option<Outcome>,
+}
+
+impl Exporter {
+    const LINE_LENGTH: usize = 80;
+
+    fn new() -> Exporter {
+        Exporter {
+            out: Vec::new(),
+            line: 0,
+            headers: false,
+            turn: Color::White,
+            fullmoves: 1,
+            number: true,
+            open: 0,
+            outcome: None,
+        }
+    }
+
+    fn token(&mut self, token: &[u8]) {
+        let prefix = self.open;
+        let len = prefix + token.len();
+        if self.line > 0 && self.line + 1 + len > Exporter::LINE_LENGTH {
+            self.out.push(b'\n');
+            self.line = 0;
+        } else if self.line > 0 {
+            self.out.push(b' ');
+            self.line += 1;
+        }
+        self.out.extend((0..prefix).map(|_| b'('));
+        self.out.extend_from_slice(token);
+        self.line += len;
+        self.open = 0;
+    }
+}
+
+impl Visitor for Exporter {
+    type Result = Vec<u8>;
+
+    fn begin_game(&mut self) {
+        *self = Exporter::new();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        if key == b"FEN" {
+            if let Ok(fen) = Fen::from_ascii(value.as_bytes()) {
+                self.turn = fen.as_setup().turn;
+                self.fullmoves = fen.as_setup().fullmoves.get();
+            }
+        } else if key == b"Result" {
+            // Used if the movetext has no termination marker.
+            self.outcome = Outcome::from_ascii(value.as_bytes()).ok();
+        }
+
+        self.out.push(b'[');
+        self.out.extend_from_slice(key);
+        self.out.extend_from_slice(b" \"");
+        self.out.extend_from_slice(value.as_bytes());
+        self.out.extend_from_slice(b"\"]\n");
+        self.headers = true;
+    }
+
+    fn end_headers(&mut self) -> Skip {
+        if self.headers {
+            self.out.push(b'\n');
+        }
+        Skip(false)
+    }
+
+    fn san_at(&mut self, ply: usize, san_plus: SanPlus) {
+        let ply = ply + usize::from(self.turn.is_black());
+        let number = self.fullmoves as usize + ply / 2;
+        if ply.is_multiple_of(2) {
+            self.token(format!("{}.", number).as_bytes());
+        } else if self.number {
+            self.token(format!("{}...", number).as_bytes());
+        }
+        self.token(san_plus.to_string().as_bytes());
+        self.number = false;
+    }
+
+    fn nag(&mut self, nag: Nag) {
+        self.token(nag.to_string().as_bytes());
+    }
+
+    fn comment(&mut self, comment: RawComment<'_>) {
+        let text = comment.decode();
+        let mut words = text.split(|&ch| ch == b' ').filter(|word| !word.is_empty());
+        match words.next() {
+            Some(first) => {
+                self.token(&[b"{", first].concat());
+                for word in words {
+                    self.token(word);
+                }
+                self.out.push(b'}');
+                self.line += 1;
+            }
+            None => self.token(b"{}"),
+        }
+        self.number = true;
+    }
+
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+        self.open += 1;
+        self.number = true;
+        Skip(false)
+    }
+
+    fn end_variation(&mut self) {
+        if self.open > 0 {
+            // Empty variation.
+            self.open -= 1;
+        } else {
+            self.out.push(b')');
+            self.line += 1;
+        }
+        self.number = true;
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.outcome = outcome;
+    }
+
+    fn end_game(&mut self) -> Vec<u8> {
+        self.open = 0;
+        match self.outcome {
+            Some(outcome) => self.token(outcome.to_string().as_bytes()),
+            None => self.token(b"*"),
+        }
+        self.out.push(b'\n');
+        self.out.split_off(0)
+    }
+}
+
+fn stats(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &[], &[])?;
+    for input in args.inputs() {
+        let mut stats = Stats::default();
+        open(input)?.read_all(&mut stats)?;
+        println!(
+            "{}",
+            json!({
+                "input": input,
+                "games": stats.games,
+                "headers": stats.headers,
+                "moves": stats.moves,
+                "nags": stats.nags,
+                "comments": stats.comments,
+                "variations": stats.variations,
+                "white": stats.outcomes.white,
+                "draws": stats.outcomes.draws,
+                "black": stats.outcomes.black,
+            })
+        );
+    }
+    Ok(0)
+}
+
+fn validate(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &[], &[])?;
+    let mut status = 0;
+    let mut mainline = Mainline::new();
+    for input in args.inputs() {
+        let mut reader = open(input)?;
+        while reader.has_more()? {
+            let game = reader.games_read();
+            let offset = reader.bytes_consumed();
+            let (error, fatal) = match reader.read_game(&mut mainline) {
+                Ok(Some(Ok(_))) => continue,
+                Ok(Some(Err(error))) => (error, false),
+                Ok(None) => break,
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => (err.to_string(), true),
+                Err(err) => return Err(err.into()),
+            };
+            println!(
+                "{}",
+                json!({
+                    "input": input,
+                    "game": game,
+                    "offset": offset,
+                    "error": error,
+                })
+            );
+            status = 1;
+            if fatal {
+                break;
+            }
+        }
+    }
+    Ok(status)
+}
+
+fn filter(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let mut args = Args::parse(args, &[], &[])?;
+    if args.inputs.is_empty() {
+        return Err("missing filter expression".into());
+    }
+    let expr = args.inputs.remove(0);
+    let filter: Filter = expr
+        .parse()
+        .map_err(|err| format!("{} in {:?}", err, expr))?;
+
+    let mut writer = GameWriter::new(BufWriter::new(io::stdout().lock()));
+    let mut headers = Headers::new();
+    for input in args.inputs() {
+        let mut reader = open(input)?;
+        let mut games = reader.raw_games();
+        loop {
+            headers.clear();
+            let game = match games.next_with_headers(&mut headers) {
+                Some(game) => game?,
+                None => break,
+            };
+            if filter.matches(|key| headers.get(key)) {
+                writer.write(&game)?;
+            }
+        }
+    }
+    writer.finish()?;
+    Ok(0)
+}
+
+fn split(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &["-n", "-o"], &[])?;
+    let size = args.number("-n", 1000)?;
+    let prefix = args.values.get("-o").map_or("split-", String::as_str);
+
+    let mut chunk: Option<(String, GameWriter<BufWriter<File>>)> = None;
+    let mut chunks = 0;
+    for input in args.inputs() {
+        for game in open(input)?.raw_games() {
+            let game = game?;
+            let (_, writer) = match chunk {
+                Some((_, ref writer)) if writer.games < size => chunk.as_mut().unwrap(),
+                _ => {
+                    if let Some((path, writer)) = chunk.take() {
+                        finish_chunk(&path, writer)?;
+                    }
+                    chunks += 1;
+                    let path = format!("{}{:04}.pgn", prefix, chunks);
+                    let file = BufWriter::new(File::create(&path)?);
+                    chunk.insert((path, GameWriter::new(file)))
+                }
+            };
+            writer.write(&game)?;
+        }
+    }
+    if let Some((path, writer)) = chunk {
+        finish_chunk(&path, writer)?;
+    }
+    Ok(0)
+}
+
+fn finish_chunk(path: &str, writer: GameWriter<BufWriter<File>>) -> io::Result<()> {
+    let games = writer.finish()?;
+    println!("{}", json!({ "path": path, "games": games }));
+    Ok(())
+}
+
+fn merge(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &[], &[])?;
+    let mut writer = GameWriter::new(BufWriter::new(io::stdout().lock()));
+    for input in args.inputs() {
+        for game in open(input)?.raw_games() {
+            writer.write(&game?)?;
+        }
+    }
+    writer.finish()?;
+    Ok(0)
+}
+
+fn dedup(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &[], &["--report"])?;
+    let inputs = args.inputs();
+    if inputs.contains(&"-") {
+        // Inputs are read twice.
+        return Err("dedup cannot read from stdin".into());
+    }
+
+    let mut dedup = Dedup::new();
+    let mut sources = Vec::new();
+    for input in &inputs {
+        let first = dedup.len() as u64;
+        dedup.add_games(&mut open(input)?)?;
+        sources.push((first, *input));
+    }
+    let source = |game: u64| {
+        let (first, input) = sources[sources.partition_point(|&(first, _)| first <= game) - 1];
+        (input, game - first)
+    };
+
+    let clusters = dedup.clusters();
+    if args.switches.contains("--report") {
+        for cluster in clusters {
+            let games = cluster
+                .games
+                .iter()
+                .zip(&cluster.offsets)
+                .map(|(&game, offset)| {
+                    let (input, index) = source(game);
+                    json!({ "input": input, "game": index, "offset": offset })
+                })
+                .collect::<Vec<_>>();
+            let keep = cluster.games.iter().position(|&game| game == cluster.keep);
+            println!(
+                "{}",
+                json!({ "keep": keep.unwrap_or_default(), "games": games })
+            );
+        }
+        return Ok(0);
+    }
+
+    let dropped: HashSet<u64> = clusters
+        .into_iter()
+        .flat_map(|cluster| {
+            let keep = cluster.keep;
+            cluster.games.into_iter().filter(move |&game| game != keep)
+        })
+        .collect();
+
+    let mut writer = GameWriter::new(BufWriter::new(io::stdout().lock()));
+    let mut index = 0;
+    for input in inputs {
+        for game in open(input)?.raw_games() {
+            let game = game?;
+            if !dropped.contains(&index) {
+                writer.write(&game)?;
+            }
+            index += 1;
+        }
+    }
+    writer.finish()?;
+    Ok(0)
+}
+
+fn convert(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &["--format"], &[])?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    match args.values.get("--format").map_or("pgn", String::as_str) {
+        "pgn" => {
+            let mut writer = GameWriter::new(out);
+            let mut exporter = Exporter::new();
+            for input in args.inputs() {
+                let mut reader = open(input)?;
+                while let Some(game) = reader.read_game(&mut exporter)? {
+                    writer.write(&game)?;
+                }
+            }
+            writer.finish()?;
+        }
+        "json" => {
+            for input in args.inputs() {
+                open(input)?.write_ndjson(&mut out)?;
+            }
+        }
+        "uci" => {
+            let mut mainline = Mainline::new();
+            for input in args.inputs() {
+                let mut reader = open(input)?;
+                while let Some(moves) = reader.read_game(&mut mainline)? {
+                    let moves = moves.map_err(|err| format!("{}: {}", input, err))?;
+                    let moves = moves
+                        .into_iter()
+                        .map(|m| UciMove::from_standard(m).to_string())
+                        .collect::<Vec<_>>();
+                    writeln!(out, "{}", moves.join(" "))?;
+                }
+            }
+            out.flush()?;
+        }
+        format => return Err(format!("unknown format {}", format).into()),
+    }
+    Ok(0)
+}
+
+fn index(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &["-o"], &[])?;
+    let input = match args.inputs[..] {
+        [ref input] if input != "-" => input,
+        _ => return Err("index requires exactly one uncompressed file".into()),
+    };
+    let path = args
+        .values
+        .get("-o")
+        .cloned()
+        .unwrap_or_else(|| format!("{}.idx", input));
+
+    let mut index = match HeaderIndex::open(&path) {
+        Ok(index) => index,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => HeaderIndex::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let added = index.update(input)?;
+    index.save(&path)?;
+    println!(
+        "{}",
+        json!({ "index": path, "games": index.len(), "added": added })
+    );
+    Ok(0)
+}
+
+fn head(args: Vec<String>) -> Result<i32, Box<dyn Error>> {
+    let args = Args::parse(args, &["-n"], &[])?;
+    let count = args.number("-n", 10)?;
+    let mut writer = GameWriter::new(BufWriter::new(io::stdout().lock()));
+    'inputs: for input in args.inputs() {
+        for game in open(input)?.raw_games() {
+            if writer.games >= count {
+                break 'inputs;
+            }
+            writer.write(&game?)?;
+        }
+    }
+    writer.finish()?;
+    Ok(0)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next().unwrap_or_default();
+    let args = args.collect();
+
+    let result = match command.as_str() {
+        "stats" => stats(args),
+        "validate" => validate(args),
+        "filter" => filter(args),
+        "split" => split(args),
+        "merge" => merge(args),
+        "dedup" => dedup(args),
+        "convert" => convert(args),
+        "index" => index(args),
+        "head" => head(args),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            Ok(2)
+        }
+    };
+
+    match result {
+        Ok(status) => process::exit(status),
+        Err(err)
+            if err.downcast_ref::<io::Error>().map(io::Error::kind)
+                == Some(io::ErrorKind::BrokenPipe) =>
+        {
+            process::exit(0)
+        }
+        Err(err) => {
+            eprintln!("pgn: {}", err);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export() {
+        let pgn = b"[Event \"?\"]\n[FEN \"8/8/8/8/8/4k3/8/R3K3 b - - 0 40\"]\n\n40...Kd3 {  good\n move } 41.Ra3+ ( 41. Kf1 $1 ) ( ) Ke4 1/2-1/2";
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let game = reader.read_game(&mut Exporter::new()).unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8(game).unwrap(),
+            "[Event \"?\"]\n[FEN \"8/8/8/8/8/4k3/8/R3K3 b - - 0 40\"]\n\n40... Kd3 {good move} 41. Ra3+ (41. Kf1 $1) 41... Ke4 1/2-1/2\n"
+        );
+
+        let pgn = b"[Result \"1-0\"]\n\n1. e4\n\n[Result \"1-0\"]\n\n1. d4 *";
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let game = reader.read_game(&mut Exporter::new()).unwrap().unwrap();
+        assert_eq!(game, b"[Result \"1-0\"]\n\n1. e4 1-0\n");
+        let game = reader.read_game(&mut Exporter::new()).unwrap().unwrap();
+        assert_eq!(game, b"[Result \"1-0\"]\n\n1. d4 *\n");
+    }
+}
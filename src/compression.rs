@@ -0,0 +1,228 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fs::File,
+    io::{self, Cursor, Read},
+    path::Path,
+};
+
+use crate::reader::BufferedReader;
+
+/// Longest magic number of any supported format.
+const MAX_MAGIC_SIZE: usize = 6;
+
+/// A compression format that can be detected from the leading bytes of a
+/// stream.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Compression {
+    /// bzip2, including multi-member streams as produced by parallel
+    /// compressors and used for the lichess database dumps.
+    Bzip2,
+    /// xz.
+    Xz,
+    /// gzip, including multi-member streams.
+    Gzip,
+    /// lz4 frame format.
+    Lz4,
+    /// Zstandard.
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression format from the magic number at the start of
+    /// a stream. Returns `None` for anything else, in particular for
+    /// uncompressed PGNs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::Compression;
+    ///
+    /// assert_eq!(Compression::from_magic(b"BZh91AY&SY"), Some(Compression::Bzip2));
+    /// assert_eq!(Compression::from_magic(b"[Event \"?\"]"), None);
+    /// ```
+    pub fn from_magic(bytes: &[u8]) -> Option<Compression> {
+        if bytes.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else if bytes.starts_with(b"\xfd7zXZ\x00") {
+            Some(Compression::Xz)
+        } else if bytes.starts_with(b"\x1f\x8b") {
+            Some(Compression::Gzip)
+        } else if bytes.starts_with(b"\x04\x22\x4d\x18") {
+            Some(Compression::Lz4)
+        } else if bytes.starts_with(b"\x28\xb5\x2f\xfd") {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn feature(self) -> &'static str {
+        match self {
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+            Compression::Gzip => "gzip",
+            Compression::Lz4 => "lz4",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    fn decoder<R: Read + Send + 'static>(self, inner: R) -> io::Result<Box<dyn Read + Send>> {
+        Ok(match self {
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(inner)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(inner)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(inner)),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Box::new(lz4::Decoder::new(inner)?),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(inner)?),
+            #[allow(unreachable_patterns)]
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "{:?} compressed input, but feature {:?} of pgn-reader is not enabled",
+                        self,
+                        self.feature()
+                    ),
+                ))
+            }
+        })
+    }
+}
+
+impl BufferedReader<Box<dyn Read + Send>> {
+    /// Open a file and create a reader that transparently decompresses it,
+    /// if required.
+    ///
+    /// See [`from_reader_auto()`](#method.from_reader_auto).
+    ///
+    /// ```
+    /// # use std::io;
+    /// # fn try_main() -> io::Result<()> {
+    /// use pgn_reader::BufferedReader;
+    ///
+    /// let reader = BufferedReader::open_auto("lichess_db_standard_rated_2018-10.pgn.zst")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * I/O error when opening or reading the file.
+    /// * The file is compressed with a format whose crate feature is not
+    ///   enabled.
+    pub fn open_auto<P: AsRef<Path>>(path: P) -> io::Result<BufferedReader<Box<dyn Read + Send>>> {
+        BufferedReader::from_reader_auto(File::open(path)?)
+    }
+
+    /// Create a new reader that detects the compression format of `inner`
+    /// by its magic number and wraps the matching decoder. Uncompressed
+    /// input is read as is.
+    ///
+    /// Each format requires the corresponding crate feature (`bzip2`, `xz`,
+    /// `gzip`, `lz4`, `zstd`, or all of them with `compression`).
+    ///
+    /// # Errors
+    ///
+    /// * I/O error when reading the magic number.
+    /// * The input is compressed with a format whose crate feature is not
+    ///   enabled.
+    pub fn from_reader_auto<R: Read + Send + 'static>(
+        inner: R,
+    ) -> io::Result<BufferedReader<Box<dyn Read + Send>>> {
+        decompress_auto(inner).map(BufferedReader::new)
+    }
+}
+
+/// Wraps `inner` in the decoder matching its magic number, or returns it as
+/// is if it is not compressed.
+pub(crate) fn decompress_auto<R: Read + Send + 'static>(
+    mut inner: R,
+) -> io::Result<Box<dyn Read + Send>> {
+    let mut magic = [0; MAX_MAGIC_SIZE];
+    let mut len = 0;
+    while len < MAX_MAGIC_SIZE {
+        match inner.read(&mut magic[len..]) {
+            Ok(0) => break,
+            Ok(size) => len += size,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    let inner = Cursor::new(magic).take(len as u64).chain(inner);
+    match Compression::from_magic(&magic[..len]) {
+        Some(compression) => compression.decoder(inner),
+        None => Ok(Box::new(inner)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::visitor::Visitor;
+
+    struct GameCounter {
+        count: usize,
+    }
+
+    impl Visitor for GameCounter {
+        type Result = ();
+
+        fn end_game(&mut self) {
+            self.count += 1;
+        }
+    }
+
+    #[test]
+    fn test_uncompressed() -> io::Result<()> {
+        let mut counter = GameCounter { count: 0 };
+        let mut reader = BufferedReader::from_reader_auto(Cursor::new(b"1. e4 *\n\n1. d4 *"))?;
+        reader.read_all(&mut counter)?;
+        assert_eq!(counter.count, 2);
+
+        let mut reader = BufferedReader::from_reader_auto(Cursor::new(b"*"))?;
+        reader.read_all(&mut counter)?;
+        assert_eq!(counter.count, 3);
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_multi_member_gzip() -> io::Result<()> {
+        use std::io::Write;
+
+        use flate2::{write::GzEncoder, Compression as Level};
+
+        let mut compressed = Vec::new();
+        for game in [&b"1. e4 *\n\n"[..], &b"1. d4 *\n"[..]] {
+            let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+            encoder.write_all(game)?;
+            compressed.extend(encoder.finish()?);
+        }
+
+        let mut counter = GameCounter { count: 0 };
+        let mut reader = BufferedReader::from_reader_auto(Cursor::new(compressed))?;
+        reader.read_all(&mut counter)?;
+        assert_eq!(counter.count, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,450 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    mem,
+};
+
+use crate::{
+    tokenizer::{TokenKind, Tokenizer},
+    types::{escape_header_value, RawComment, RawHeader},
+};
+
+/// A token of a [`Document`](struct.Document.html), owning its bytes.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Node {
+    kind: TokenKind,
+    text: Vec<u8>,
+}
+
+impl Node {
+    /// Create a node. It is up to the caller to ensure that `text` is
+    /// lexed as a single token of the given kind.
+    pub fn new<T: Into<Vec<u8>>>(kind: TokenKind, text: T) -> Node {
+        Node {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    /// Returns the kind of token.
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    /// Returns the bytes of the token, exactly as in the source.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// Replaces the bytes of the token. It is up to the caller to ensure
+    /// that `text` is still lexed as a single token of the same kind.
+    pub fn set_text<T: Into<Vec<u8>>>(&mut self, text: T) {
+        self.text = text.into();
+    }
+
+    fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Bom | TokenKind::Whitespace | TokenKind::Escape
+        )
+    }
+}
+
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Node")
+            .field(&self.kind)
+            .field(&String::from_utf8_lossy(&self.text))
+            .finish()
+    }
+}
+
+/// A single game of a [`Document`](struct.Document.html), including
+/// leading escape lines and whitespace and the whitespace separating it
+/// from the next game.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Game {
+    nodes: Vec<Node>,
+}
+
+impl Game {
+    /// Returns the nodes of the game.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Returns the nodes of the game for arbitrary edits.
+    pub fn nodes_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.nodes
+    }
+
+    /// Iterates over the headers of the game, with the raw values between
+    /// the quotes.
+    pub fn headers(&self) -> impl Iterator<Item = (&[u8], RawHeader<'_>)> {
+        self.header_positions().map(move |(name, value)| {
+            (
+                self.nodes[name].text(),
+                RawHeader(unquote(self.nodes[value].text())),
+            )
+        })
+    }
+
+    /// Returns the raw value of the first header with the given key.
+    pub fn header(&self, key: &[u8]) -> Option<RawHeader<'_>> {
+        self.headers()
+            .find(|&(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the value of the first header with the given key, leaving
+    /// everything around it untouched. If there is no such header, a new
+    /// header is added after the existing headers.
+    ///
+    /// Quotes and backslashes in `value` are escaped.
+    pub fn set_header(&mut self, key: &[u8], value: &[u8]) {
+        let mut quoted = Vec::with_capacity(value.len() + 2);
+        quoted.push(b'"');
+        escape_header_value(value, &mut quoted);
+        quoted.push(b'"');
+
+        let existing = self
+            .header_positions()
+            .find(|&(name, _)| self.nodes[name].text() == key);
+        if let Some((_, value)) = existing {
+            self.nodes[value].set_text(quoted);
+            return;
+        }
+
+        let tag = [
+            Node::new(TokenKind::TagOpen, "["),
+            Node::new(TokenKind::TagName, key),
+            Node::new(TokenKind::Whitespace, " "),
+            Node::new(TokenKind::TagValue, quoted),
+            Node::new(TokenKind::TagClose, "]"),
+        ];
+
+        match self.last_tag_close() {
+            Some(close) => {
+                let at = close + 1;
+                self.nodes.splice(
+                    at..at,
+                    Some(Node::new(TokenKind::Whitespace, "\n"))
+                        .into_iter()
+                        .chain(tag),
+                );
+            }
+            None => {
+                let at = self
+                    .nodes
+                    .iter()
+                    .position(|node| !node.is_trivia())
+                    .unwrap_or(self.nodes.len());
+                self.nodes.splice(
+                    at..at,
+                    tag.into_iter()
+                        .chain(Some(Node::new(TokenKind::Whitespace, "\n\n"))),
+                );
+            }
+        }
+    }
+
+    /// Removes the first header with the given key, together with its line
+    /// break. Returns whether a header was removed.
+    pub fn remove_header(&mut self, key: &[u8]) -> bool {
+        let name = match self
+            .header_positions()
+            .find(|&(name, _)| self.nodes[name].text() == key)
+        {
+            Some((name, _)) => name,
+            None => return false,
+        };
+
+        let open = self.nodes[..name]
+            .iter()
+            .rposition(|node| node.kind == TokenKind::TagOpen)
+            .unwrap_or(name);
+        let end = self.nodes[name..]
+            .iter()
+            .position(|node| node.kind == TokenKind::TagClose)
+            .map_or(name + 1, |close| name + close + 1);
+        self.nodes.drain(open..end);
+
+        if let Some(node) = self.nodes.get_mut(open) {
+            if node.kind == TokenKind::Whitespace {
+                let skip = if node.text.starts_with(b"\r\n") {
+                    2
+                } else if node.text.starts_with(b"\n") {
+                    1
+                } else {
+                    0
+                };
+                node.text.drain(..skip);
+                if node.text.is_empty() {
+                    self.nodes.remove(open);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Iterates over the `{ comments }` of the game, without the braces.
+    pub fn comments(&self) -> impl Iterator<Item = RawComment<'_>> {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind == TokenKind::Comment)
+            .map(|node| RawComment(&node.text[1..node.text.len() - 1]))
+    }
+
+    /// Replaces the text of the comment with the given index (counting
+    /// [`comments()`](struct.Game.html#method.comments)). Returns `false`
+    /// and leaves the game unchanged if there is no such comment, or if
+    /// `text` contains `}`, which would end the comment early.
+    pub fn set_comment(&mut self, index: usize, text: &[u8]) -> bool {
+        if text.contains(&b'}') {
+            return false;
+        }
+        match self
+            .nodes
+            .iter_mut()
+            .filter(|node| node.kind == TokenKind::Comment)
+            .nth(index)
+        {
+            Some(node) => {
+                let mut braced = Vec::with_capacity(text.len() + 2);
+                braced.push(b'{');
+                braced.extend_from_slice(text);
+                braced.push(b'}');
+                node.set_text(braced);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes the game, exactly as parsed except for edits.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for node in &self.nodes {
+            writer.write_all(&node.text)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the bytes of the game, exactly as parsed except for edits.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for node in &self.nodes {
+            bytes.extend_from_slice(&node.text);
+        }
+        bytes
+    }
+
+    /// Positions of the name and value nodes of the headers.
+    fn header_positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.kind == TokenKind::TagName)
+            .filter_map(move |(name, _)| {
+                self.nodes[name + 1..]
+                    .iter()
+                    .take_while(|node| {
+                        !matches!(node.kind, TokenKind::TagClose | TokenKind::TagOpen)
+                    })
+                    .position(|node| node.kind == TokenKind::TagValue)
+                    .map(|value| (name, name + 1 + value))
+            })
+    }
+
+    fn last_tag_close(&self) -> Option<usize> {
+        let movetext = self
+            .nodes
+            .iter()
+            .position(|node| {
+                !node.is_trivia()
+                    && !matches!(
+                        node.kind,
+                        TokenKind::TagOpen
+                            | TokenKind::TagName
+                            | TokenKind::TagValue
+                            | TokenKind::TagClose
+                    )
+            })
+            .unwrap_or(self.nodes.len());
+        self.nodes[..movetext]
+            .iter()
+            .rposition(|node| node.kind == TokenKind::TagClose)
+    }
+}
+
+fn unquote(value: &[u8]) -> &[u8] {
+    let value = value.strip_prefix(b"\"").unwrap_or(value);
+    value.strip_suffix(b"\"").unwrap_or(value)
+}
+
+/// A lossless concrete syntax tree of a PGN file, for editing tools.
+///
+/// Retains everything, including whitespace, line breaks, `;` comments,
+/// `%` escape lines, move number formatting and malformed tokens, so that
+/// serializing an unmodified document yields the original bytes. Games are
+/// delimited with the same rules as [`BufferedReader`], as tokenized by
+/// [`Tokenizer`].
+///
+/// ```
+/// use pgn_reader::Document;
+///
+/// let pgn = b"[Event \"?\"]\r\n[White \"A\"]\r\n\r\n1.e4   {  draw? } 1/2-1/2\r\n";
+///
+/// let mut document = Document::parse(pgn);
+/// assert_eq!(document.to_bytes(), &pgn[..]);
+///
+/// let game = &mut document.games_mut()[0];
+/// game.set_header(b"White", b"Deep Blue");
+/// assert!(game.set_comment(0, b" agreed "));
+/// assert!(!game.set_comment(0, b"} 1-0 {"));
+///
+/// assert_eq!(
+///     document.to_bytes(),
+///     &b"[Event \"?\"]\r\n[White \"Deep Blue\"]\r\n\r\n1.e4   { agreed } 1/2-1/2\r\n"[..]
+/// );
+/// ```
+///
+/// [`BufferedReader`]: struct.BufferedReader.html
+/// [`Tokenizer`]: struct.Tokenizer.html
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Document {
+    games: Vec<Game>,
+    trailing: Vec<Node>,
+}
+
+impl Document {
+    /// Parses a document. Never fails, because malformed input is retained
+    /// as nodes of kind [`TokenKind::Unknown`](enum.TokenKind.html#variant.Unknown).
+    pub fn parse(pgn: &[u8]) -> Document {
+        let mut document = Document::default();
+        let mut tokenizer = Tokenizer::new(pgn);
+        let mut pending = Vec::new();
+        let mut in_game = false;
+
+        while let Some(token) = tokenizer.next() {
+            let node = Node::new(token.kind, &pgn[token.span]);
+            if !in_game && !node.is_trivia() {
+                in_game = true;
+            }
+            pending.push(node);
+            if in_game && tokenizer.ends_game() {
+                document.games.push(Game {
+                    nodes: mem::take(&mut pending),
+                });
+                in_game = false;
+            }
+        }
+
+        if in_game {
+            document.games.push(Game { nodes: pending });
+        } else {
+            document.trailing = pending;
+        }
+
+        document
+    }
+
+    /// Returns the games.
+    pub fn games(&self) -> &[Game] {
+        &self.games
+    }
+
+    /// Returns the games for editing. Games can also be added, removed or
+    /// reordered.
+    pub fn games_mut(&mut self) -> &mut Vec<Game> {
+        &mut self.games
+    }
+
+    /// Returns whitespace and escape lines after the last game.
+    pub fn trailing(&self) -> &[Node] {
+        &self.trailing
+    }
+
+    /// Writes the document, exactly as parsed except for edits.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for game in &self.games {
+            game.write_to(&mut writer)?;
+        }
+        for node in &self.trailing {
+            writer.write_all(&node.text)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the bytes of the document, exactly as parsed except for
+    /// edits.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes).expect("write to vec");
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lossless() {
+        let pgn: &[u8] = b"\xef\xbb\xbf% header\n[Event \"x\"] ]\n[Bad\n\n1.e4 e5 ;c\n2.Nf3 }stray\n\n\n[Event \"y\"]\n1... ??? xyz $300 {unterminated\n  \n";
+        let document = Document::parse(pgn);
+        assert_eq!(document.games().len(), 2);
+        assert_eq!(document.to_bytes(), pgn);
+        assert!(document.trailing().is_empty());
+
+        // Like the reader, only the first line is a header.
+        let document = Document::parse(b"[A \"1\"]\n  [B \"2\"]\n\n1. e4 *");
+        assert_eq!(document.games().len(), 2);
+        assert_eq!(document.games()[0].header(b"B"), None);
+
+        let document = Document::parse(b"  \n% only\n");
+        assert!(document.games().is_empty());
+        assert_eq!(document.to_bytes(), b"  \n% only\n");
+    }
+
+    #[test]
+    fn test_headers() {
+        let mut document = Document::parse(b"[Event \"?\"]\n[White \"A\"]\n\n1. e4 *\n\n1. d4 *\n");
+
+        let game = &mut document.games_mut()[0];
+        assert_eq!(game.header(b"White"), Some(RawHeader(b"A")));
+        game.set_header(b"Black", b"\"B\"");
+        assert!(game.remove_header(b"Event"));
+        assert!(!game.remove_header(b"Event"));
+
+        document.games_mut()[1].set_header(b"Event", b"2");
+        assert!(!document.games_mut()[1].set_comment(0, b"none"));
+
+        assert_eq!(
+            document.to_bytes(),
+            &b"[White \"A\"]\n[Black \"\\\"B\\\"\"]\n\n1. e4 *\n\n[Event \"2\"]\n\n1. d4 *\n"[..]
+        );
+        let reparsed = Document::parse(&document.to_bytes());
+        assert_eq!(reparsed.games().len(), 2);
+        assert_eq!(
+            reparsed.games()[0].header(b"Black").unwrap().decode(),
+            &b"\"B\""[..]
+        );
+    }
+}
@@ -0,0 +1,467 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+    ops::Range,
+};
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    filter::compare_dates,
+    reader::BufferedReader,
+    search::Replay,
+    types::{Date, RawHeader, Skip},
+    visitor::Visitor,
+};
+
+/// Games are indexed by their first plies, up to this many, together with
+/// the surnames of the players, to find candidates for comparison.
+const KEY_PLIES: usize = 16;
+
+/// What is remembered about each game.
+#[derive(Debug, Clone, Default)]
+struct Record {
+    offset: u64,
+    // Hash of the normalized mainline after each ply.
+    plies: Vec<u64>,
+    white: String,
+    black: String,
+    date: Option<Date>,
+    result: Option<Outcome>,
+    // Number of headers with a value other than "" or "?".
+    headers: usize,
+}
+
+impl Record {
+    /// Bucket keys of the positions after the given plies, combined with
+    /// each known surname.
+    fn keys(&self, plies: Range<usize>) -> impl Iterator<Item = u64> + '_ {
+        let names = [&self.white, &self.black];
+        self.plies[plies].iter().flat_map(move |&ply| {
+            names
+                .into_iter()
+                .filter(|name| !name.is_empty())
+                .map(move |name| {
+                    let mut hasher = DefaultHasher::new();
+                    ply.hash(&mut hasher);
+                    name.hash(&mut hasher);
+                    hasher.finish()
+                })
+        })
+    }
+
+    /// Keys under which the game is found by shorter or equally long
+    /// versions.
+    fn index_keys(&self) -> impl Iterator<Item = u64> + '_ {
+        self.keys(0..self.plies.len().min(KEY_PLIES))
+    }
+
+    /// Keys under which longer or equally long versions of the game are
+    /// found.
+    fn lookup_keys(&self) -> impl Iterator<Item = u64> + '_ {
+        let plies = self.plies.len().min(KEY_PLIES);
+        self.keys(plies.saturating_sub(1)..plies)
+    }
+
+    fn is_duplicate(&self, other: &Record) -> bool {
+        let plies = self.plies.len().min(other.plies.len());
+        plies > 0
+            && self.plies[plies - 1] == other.plies[plies - 1]
+            && ((!self.white.is_empty() && self.white == other.white)
+                || (!self.black.is_empty() && self.black == other.black))
+            && similar_names(&self.white, &other.white)
+            && similar_names(&self.black, &other.black)
+            && match (self.date, other.date) {
+                (Some(a), Some(b)) => compare_dates(a, b) == Ordering::Equal,
+                _ => true,
+            }
+            && match (self.result, other.result) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+    }
+
+    /// Number of fields that are known in both games. Assumes that the
+    /// games are duplicates.
+    fn agreement(&self, other: &Record) -> usize {
+        usize::from(!self.white.is_empty() && !other.white.is_empty())
+            + usize::from(!self.black.is_empty() && !other.black.is_empty())
+            + usize::from(self.date.is_some() && other.date.is_some())
+            + usize::from(self.result.is_some() && other.result.is_some())
+    }
+
+    fn completeness(&self) -> (usize, usize) {
+        (self.plies.len(), self.headers)
+    }
+}
+
+/// Reduces a player name like `Carlsen, Magnus` or `Magnus Carlsen` to
+/// the lowercase surname.
+fn surname(name: &str) -> String {
+    let name = match name.split_once(',') {
+        Some((surname, _)) => surname,
+        None => name
+            .split(|ch: char| !ch.is_alphabetic())
+            .max_by_key(|word| word.chars().count())
+            .unwrap_or(""),
+    };
+    name.chars()
+        .filter(|ch| ch.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Compares surnames, allowing a single typo in longer names. Unknown
+/// names match anything.
+fn similar_names(a: &str, b: &str) -> bool {
+    if a.is_empty() || b.is_empty() || a == b {
+        return true;
+    }
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    if a.len().min(b.len()) < 4 || a.len().abs_diff(b.len()) > 1 {
+        return false;
+    }
+    // Levenshtein distance, one row at a time.
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()] <= 1
+}
+
+/// Builds a record for each game.
+struct Fingerprint {
+    replay: Replay,
+    hash: u64,
+    record: Record,
+}
+
+impl Visitor for Fingerprint {
+    type Result = Record;
+
+    fn begin_game(&mut self) {
+        self.replay = Replay::new();
+        self.hash = 0;
+        self.record = Record::default();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        let bytes = value.as_bytes();
+        if bytes.is_empty() || bytes == b"?" || bytes.iter().all(|&ch| ch == b'?' || ch == b'.') {
+            return;
+        }
+        self.record.headers += 1;
+        match key {
+            b"White" => self.record.white = surname(&value.decode_utf8_lossy()),
+            b"Black" => self.record.black = surname(&value.decode_utf8_lossy()),
+            b"Date" => self.record.date = Date::from_ascii(bytes).ok(),
+            b"Result" => self.record.result = Outcome::from_ascii(bytes).ok(),
+            _ => self.replay.header(key, value),
+        }
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        let mut hasher = DefaultHasher::new();
+        self.hash.hash(&mut hasher);
+        // Normalize the move if it is legal, so that different notations
+        // of the same move agree.
+        match self.replay.play(&san_plus) {
+            Some(m) => m.hash(&mut hasher),
+            None => san_plus.san.hash(&mut hasher),
+        }
+        self.hash = hasher.finish();
+        self.record.plies.push(self.hash);
+    }
+
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+        Skip(true) // stay in the mainline
+    }
+
+    fn end_game(&mut self) -> Record {
+        std::mem::take(&mut self.record)
+    }
+}
+
+/// A group of games that are duplicates of each other.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DuplicateCluster {
+    /// Indexes of the games, in order.
+    pub games: Vec<u64>,
+    /// Byte offsets of the games, in the same order.
+    pub offsets: Vec<u64>,
+    /// Index of the most complete version: the game with the most plies,
+    /// and then the most informative headers.
+    pub keep: u64,
+}
+
+/// Finds duplicate games in merged collections.
+///
+/// Games are fingerprinted by their mainline moves, normalized by
+/// replaying them, so that `Nbd7` and `N8d7` or a missing `+` do not
+/// matter. Two games are duplicates if:
+///
+/// * The moves of one are a prefix of the moves of the other, so that
+///   truncated copies are found.
+/// * The surname of at least one player is known in both games and spelled
+///   the same.
+/// * The surnames of both players agree as far as known, allowing one
+///   typo.
+/// * Dates agree as far as both are known, like `2019.??.??` and
+///   `2019.05.01`.
+/// * Results agree, unless unknown.
+///
+/// ```
+/// use pgn_reader::{BufferedReader, Dedup};
+///
+/// let pgn = b"[White \"Carlsen, Magnus\"]\n[Date \"2019.05.01\"]\n\n1. e4 e5 2. Nf3 Nc6 1-0\n\n[White \"Carlsen, M.\"]\n[Date \"2019.??.??\"]\n\n1. e4 e5 2. Nf3 *\n\n[White \"Carlsen, Magnus\"]\n\n1. d4 *\n";
+///
+/// let mut dedup = Dedup::new();
+/// dedup.add_games(&mut BufferedReader::new_cursor(&pgn[..]))?;
+///
+/// let clusters = dedup.clusters();
+/// assert_eq!(clusters.len(), 1);
+/// assert_eq!(clusters[0].games, [0, 1]);
+/// assert_eq!(clusters[0].keep, 0);
+///
+/// let mut unique = Vec::new();
+/// dedup.write_unique(&mut BufferedReader::new_cursor(&pgn[..]), &mut unique)?;
+/// assert_eq!(unique, &b"[White \"Carlsen, Magnus\"]\n[Date \"2019.05.01\"]\n\n1. e4 e5 2. Nf3 Nc6 1-0\n\n[White \"Carlsen, Magnus\"]\n\n1. d4 *\n"[..]);
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Dedup {
+    records: Vec<Record>,
+}
+
+impl Dedup {
+    /// Create an empty deduplicator.
+    pub fn new() -> Dedup {
+        Dedup::default()
+    }
+
+    /// Returns the number of games added so far.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns whether no games have been added.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Fingerprints all remaining games of `reader`. Returns the number of
+    /// games.
+    ///
+    /// Game indexes continue across calls, so several sources can be
+    /// compared by adding them in order.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors. Games before the error remain
+    ///   fingerprinted.
+    pub fn add_games<R: Read>(&mut self, reader: &mut BufferedReader<R>) -> io::Result<u64> {
+        let mut fingerprint = Fingerprint {
+            replay: Replay::new(),
+            hash: 0,
+            record: Record::default(),
+        };
+
+        let mut games = 0;
+        while reader.has_more()? {
+            let offset = reader.bytes_consumed();
+            if let Some(mut record) = reader.read_game(&mut fingerprint)? {
+                record.offset = offset;
+                self.records.push(record);
+            }
+            games += 1;
+        }
+        Ok(games)
+    }
+
+    /// Groups the games added so far into clusters of duplicates. Games
+    /// without duplicates are not included.
+    ///
+    /// Each game is attached to its single best match among the more
+    /// complete versions, if any, so that games with little information
+    /// do not join unrelated clusters.
+    pub fn clusters(&self) -> Vec<DuplicateCluster> {
+        let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (i, record) in self.records.iter().enumerate() {
+            for key in record.index_keys() {
+                buckets.entry(key).or_default().push(i);
+            }
+        }
+
+        // More complete, and then earlier games are better.
+        let rank = |i: usize| (self.records[i].completeness(), Reverse(i));
+
+        let mut parent: Vec<usize> = (0..self.records.len()).collect();
+        let mut candidates = Vec::new();
+        for (i, record) in self.records.iter().enumerate() {
+            candidates.clear();
+            for key in record.lookup_keys() {
+                candidates.extend(buckets.get(&key).into_iter().flatten().copied());
+            }
+            candidates.sort_unstable();
+            candidates.dedup();
+
+            if let Some(best) = candidates
+                .iter()
+                .copied()
+                .filter(|&j| rank(j) > rank(i) && record.is_duplicate(&self.records[j]))
+                .max_by_key(|&j| (record.agreement(&self.records[j]), rank(j)))
+            {
+                parent[i] = best;
+            }
+        }
+
+        // Follow the parents to the most complete version. Parents always
+        // rank higher, so there are no cycles.
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        let mut clusters: Vec<DuplicateCluster> = Vec::new();
+        let mut cluster_of: HashMap<usize, usize> = HashMap::new();
+        for (i, record) in self.records.iter().enumerate() {
+            let keep = root(&mut parent, i);
+            let index = *cluster_of.entry(keep).or_insert_with(|| {
+                clusters.push(DuplicateCluster {
+                    games: Vec::new(),
+                    offsets: Vec::new(),
+                    keep: keep as u64,
+                });
+                clusters.len() - 1
+            });
+            let cluster = &mut clusters[index];
+            cluster.games.push(i as u64);
+            cluster.offsets.push(record.offset);
+        }
+        clusters.retain(|cluster| cluster.games.len() > 1);
+        clusters
+    }
+
+    /// Copies the games of `reader` to `writer`, leaving out all duplicates
+    /// but the most complete version of each. Returns the number of games
+    /// written.
+    ///
+    /// `reader` must yield the same games that have been added, in the
+    /// same order. Games are copied verbatim and separated by empty lines.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader or from `writer`.
+    pub fn write_unique<R: Read, W: Write>(
+        &self,
+        reader: &mut BufferedReader<R>,
+        mut writer: W,
+    ) -> io::Result<u64> {
+        let dropped: HashSet<u64> = self
+            .clusters()
+            .into_iter()
+            .flat_map(|cluster| {
+                let keep = cluster.keep;
+                cluster.games.into_iter().filter(move |&game| game != keep)
+            })
+            .collect();
+
+        let mut written = 0;
+        for (i, game) in reader.raw_games().enumerate() {
+            let game = game?;
+            if dropped.contains(&(i as u64)) {
+                continue;
+            }
+            if written > 0 {
+                writer.write_all(b"\n")?;
+            }
+            writer.write_all(&game)?;
+            if !game.ends_with(b"\n") {
+                writer.write_all(b"\n")?;
+            }
+            written += 1;
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(surname("Carlsen, Magnus"), "carlsen");
+        assert_eq!(surname("Magnus Carlsen"), "carlsen");
+        assert_eq!(surname("Carlsen M."), "carlsen");
+        assert!(similar_names("kasparov", "kasparow"));
+        assert!(similar_names("", "anand"));
+        assert!(!similar_names("kasparov", "karpov"));
+        assert!(!similar_names("tal", "tai"));
+    }
+
+    #[test]
+    fn test_clusters() -> io::Result<()> {
+        let moves =
+            "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O";
+        let pgn = format!(
+            "[White \"Anand, V.\"]\n[Black \"Kramnik, V.\"]\n\n{moves} *\n\n\
+             [Event \"?\"]\n[White \"Kasparov, Garry\"]\n[Black \"Karpov, Anatoly\"]\n[Result \"1-0\"]\n\n{moves} 9. h3 Nb8 10. d4 Nbd7 1-0\n\n\
+             [White \"Kasparow, G.\"]\n[Black \"Karpov\"]\n[Result \"*\"]\n\n{moves} *\n\n\
+             [White \"Kasparov\"]\n[Black \"Short\"]\n[Result \"0-1\"]\n\n{moves} 0-1\n\n\
+             [White \"Kasparov\"]\n[Black \"Karpov\"]\n\n{moves} 9. h3 Nb8 10. d4 N8d7+ *\n"
+        );
+
+        let mut dedup = Dedup::new();
+        assert_eq!(dedup.add_games(&mut BufferedReader::new_cursor(&pgn))?, 5);
+
+        let clusters = dedup.clusters();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].games, [1, 2, 4]);
+        assert_eq!(clusters[0].keep, 1);
+        assert_eq!(clusters[0].offsets[0], pgn.find("[Event").unwrap() as u64);
+
+        let mut unique = Vec::new();
+        let written = dedup.write_unique(&mut BufferedReader::new_cursor(&pgn), &mut unique)?;
+        assert_eq!(written, 3);
+
+        // A fragment without names does not join unrelated games.
+        let pgn = b"[White \"Anand\"]\n[Black \"Topalov\"]\n\n1. e4 e5 2. Nf3 1-0\n\n1. e4 *\n\n[White \"Kramnik\"]\n[Black \"Leko\"]\n\n1. e4 c5 0-1\n";
+        let mut dedup = Dedup::new();
+        assert_eq!(
+            dedup.add_games(&mut BufferedReader::new_cursor(&pgn[..]))?,
+            3
+        );
+        assert!(dedup.clusters().is_empty());
+        Ok(())
+    }
+}
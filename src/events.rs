@@ -0,0 +1,526 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{self, Read};
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    reader::{BufferedReader, HeaderSpan, Line, MovetextToken, ReadPgn, SliceReader},
+    types::{Nag, RawComment, RawHeader},
+};
+
+/// An event produced by the pull parser. Corresponds to a method of
+/// [`Visitor`](trait.Visitor.html).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Event<'a> {
+    /// Start of a game.
+    BeginGame,
+    /// A game header like `[White "Deep Blue"]`.
+    Header {
+        /// The key, like `White`.
+        key: &'a [u8],
+        /// The value, like `Deep Blue`.
+        value: RawHeader<'a>,
+    },
+    /// End of the headers of a game.
+    EndHeaders,
+    /// A move, like `Nf3+`.
+    San {
+        /// Ply of the move within the current line, as in
+        /// [`Visitor::san_at()`](trait.Visitor.html#method.san_at).
+        ply: usize,
+        /// The move.
+        san_plus: SanPlus,
+    },
+    /// A numeric annotation glyph like `!?` or `$7`.
+    Nag(Nag),
+    /// A `{ comment }`.
+    Comment(RawComment<'a>),
+    /// A `(`, as in
+    /// [`Visitor::begin_variation()`](trait.Visitor.html#method.begin_variation).
+    BeginVariation {
+        /// Nesting depth of the variation, starting at 1.
+        depth: usize,
+        /// Ply of the move replaced by the variation.
+        ply: usize,
+    },
+    /// A `)`. Balanced with `BeginVariation`.
+    EndVariation,
+    /// A game termination, like `*` or `1-0`.
+    Outcome(Option<Outcome>),
+    /// End of a game.
+    EndGame,
+}
+
+/// An [`Event`](enum.Event.html) that owns its data, produced by
+/// [`BufferedReader::events()`](struct.BufferedReader.html#method.events).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum OwnedEvent {
+    /// See [`Event::BeginGame`](enum.Event.html#variant.BeginGame).
+    BeginGame,
+    /// See [`Event::Header`](enum.Event.html#variant.Header).
+    Header {
+        /// The key, like `White`.
+        key: Vec<u8>,
+        /// The raw value, like `Deep Blue`.
+        value: Vec<u8>,
+    },
+    /// See [`Event::EndHeaders`](enum.Event.html#variant.EndHeaders).
+    EndHeaders,
+    /// See [`Event::San`](enum.Event.html#variant.San).
+    San {
+        /// Ply of the move within the current line.
+        ply: usize,
+        /// The move.
+        san_plus: SanPlus,
+    },
+    /// See [`Event::Nag`](enum.Event.html#variant.Nag).
+    Nag(Nag),
+    /// See [`Event::Comment`](enum.Event.html#variant.Comment).
+    Comment(Vec<u8>),
+    /// See [`Event::BeginVariation`](enum.Event.html#variant.BeginVariation).
+    BeginVariation {
+        /// Nesting depth of the variation, starting at 1.
+        depth: usize,
+        /// Ply of the move replaced by the variation.
+        ply: usize,
+    },
+    /// See [`Event::EndVariation`](enum.Event.html#variant.EndVariation).
+    EndVariation,
+    /// See [`Event::Outcome`](enum.Event.html#variant.Outcome).
+    Outcome(Option<Outcome>),
+    /// See [`Event::EndGame`](enum.Event.html#variant.EndGame).
+    EndGame,
+}
+
+impl OwnedEvent {
+    /// Borrows the event.
+    pub fn as_event(&self) -> Event<'_> {
+        match *self {
+            OwnedEvent::BeginGame => Event::BeginGame,
+            OwnedEvent::Header { ref key, ref value } => Event::Header {
+                key,
+                value: RawHeader(value),
+            },
+            OwnedEvent::EndHeaders => Event::EndHeaders,
+            OwnedEvent::San { ply, san_plus } => Event::San { ply, san_plus },
+            OwnedEvent::Nag(ref nag) => Event::Nag(nag.clone()),
+            OwnedEvent::Comment(ref comment) => Event::Comment(RawComment(comment)),
+            OwnedEvent::BeginVariation { depth, ply } => Event::BeginVariation { depth, ply },
+            OwnedEvent::EndVariation => Event::EndVariation,
+            OwnedEvent::Outcome(outcome) => Event::Outcome(outcome),
+            OwnedEvent::EndGame => Event::EndGame,
+        }
+    }
+}
+
+impl<'a> From<Event<'a>> for OwnedEvent {
+    fn from(event: Event<'a>) -> OwnedEvent {
+        match event {
+            Event::BeginGame => OwnedEvent::BeginGame,
+            Event::Header { key, value } => OwnedEvent::Header {
+                key: key.to_vec(),
+                value: value.as_bytes().to_vec(),
+            },
+            Event::EndHeaders => OwnedEvent::EndHeaders,
+            Event::San { ply, san_plus } => OwnedEvent::San { ply, san_plus },
+            Event::Nag(nag) => OwnedEvent::Nag(nag),
+            Event::Comment(comment) => OwnedEvent::Comment(comment.as_bytes().to_vec()),
+            Event::BeginVariation { depth, ply } => OwnedEvent::BeginVariation { depth, ply },
+            Event::EndVariation => OwnedEvent::EndVariation,
+            Event::Outcome(outcome) => OwnedEvent::Outcome(outcome),
+            Event::EndGame => OwnedEvent::EndGame,
+        }
+    }
+}
+
+/// An event with data relative to the start of the buffer.
+enum RawEvent {
+    BeginGame,
+    Header(HeaderSpan),
+    EndHeaders,
+    San { ply: usize, san_plus: SanPlus },
+    Nag(Nag),
+    Comment(usize),
+    BeginVariation { depth: usize, ply: usize },
+    EndVariation,
+    Outcome(Option<Outcome>),
+    EndGame,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Phase {
+    Games,
+    Headers,
+    Movetext,
+    /// End of the movetext, closing open variations.
+    EndMovetext,
+}
+
+/// The state machine behind the event iterators. Reads one event at a time
+/// with the same tokenizer as the `Visitor` based API.
+#[derive(Debug)]
+struct EventParser {
+    phase: Phase,
+    line: Line,
+    pending_header: Option<HeaderSpan>,
+    pending_comment: Option<usize>,
+}
+
+impl EventParser {
+    fn new() -> EventParser {
+        EventParser {
+            phase: Phase::Games,
+            line: Line::default(),
+            pending_header: None,
+            pending_comment: None,
+        }
+    }
+
+    fn next<P: ReadPgn>(&mut self, reader: &mut P) -> Result<Option<RawEvent>, P::Err> {
+        let result = self.advance(reader);
+        if result.is_err() {
+            // The reader has already skipped past the error.
+            self.phase = Phase::Games;
+            self.line = Line::default();
+        }
+        result
+    }
+
+    fn advance<P: ReadPgn>(&mut self, reader: &mut P) -> Result<Option<RawEvent>, P::Err> {
+        // Consume the event borrowed from the buffer by the previous call.
+        if let Some(header) = self.pending_header.take() {
+            reader.end_header(&header)?;
+        }
+        if let Some(len) = self.pending_comment.take() {
+            reader.consume(len + 1);
+        }
+
+        Ok(Some(match self.phase {
+            Phase::Games => {
+                reader.skip_bom()?;
+                reader.skip_whitespace()?;
+                if reader.fill_buffer_and_peek()?.is_none() {
+                    return Ok(None);
+                }
+                self.phase = Phase::Headers;
+                RawEvent::BeginGame
+            }
+            Phase::Headers => match reader.read_header()? {
+                Some(header) => {
+                    self.pending_header = Some(header.clone());
+                    RawEvent::Header(header)
+                }
+                None => {
+                    self.phase = Phase::Movetext;
+                    RawEvent::EndHeaders
+                }
+            },
+            Phase::Movetext => loop {
+                match reader.read_token()? {
+                    Some(MovetextToken::San(san_plus)) => {
+                        break RawEvent::San {
+                            ply: self.line.san(),
+                            san_plus,
+                        }
+                    }
+                    Some(MovetextToken::Nag(nag)) => break RawEvent::Nag(nag),
+                    Some(MovetextToken::Comment(len)) => {
+                        self.pending_comment = Some(len);
+                        break RawEvent::Comment(len);
+                    }
+                    Some(MovetextToken::BeginVariation) => {
+                        let (depth, ply) = self.line.begin_variation();
+                        break RawEvent::BeginVariation { depth, ply };
+                    }
+                    Some(MovetextToken::EndVariation) => {
+                        if self.line.end_variation() {
+                            break RawEvent::EndVariation;
+                        }
+                    }
+                    Some(MovetextToken::Outcome(outcome)) => break RawEvent::Outcome(outcome),
+                    None => {
+                        self.phase = Phase::EndMovetext;
+                        return self.advance(reader);
+                    }
+                }
+            },
+            Phase::EndMovetext => {
+                if self.line.end_variation() {
+                    RawEvent::EndVariation
+                } else {
+                    reader.skip_whitespace()?;
+                    self.phase = Phase::Games;
+                    self.line = Line::default();
+                    RawEvent::EndGame
+                }
+            }
+        }))
+    }
+}
+
+fn borrow_event(raw: RawEvent, buffer: &[u8]) -> Event<'_> {
+    match raw {
+        RawEvent::BeginGame => Event::BeginGame,
+        RawEvent::Header(header) => Event::Header {
+            key: header.key(buffer),
+            value: RawHeader(header.value(buffer)),
+        },
+        RawEvent::EndHeaders => Event::EndHeaders,
+        RawEvent::San { ply, san_plus } => Event::San { ply, san_plus },
+        RawEvent::Nag(nag) => Event::Nag(nag),
+        RawEvent::Comment(len) => Event::Comment(RawComment(&buffer[..len])),
+        RawEvent::BeginVariation { depth, ply } => Event::BeginVariation { depth, ply },
+        RawEvent::EndVariation => Event::EndVariation,
+        RawEvent::Outcome(outcome) => Event::Outcome(outcome),
+        RawEvent::EndGame => Event::EndGame,
+    }
+}
+
+/// A pull parser over PGN in memory, yielding [`Event`]s that borrow from
+/// the input.
+///
+/// After an error, parsing continues with the next game.
+///
+/// ```
+/// use pgn_reader::{Event, Events};
+///
+/// let pgn = b"[White \"Deep Blue\"]\n\n1. e4 { best by test } 1-0";
+///
+/// for event in Events::new(pgn) {
+///     match event? {
+///         Event::Header { key, value } => {
+///             assert_eq!(key, b"White");
+///             assert_eq!(value.decode_utf8_lossy(), "Deep Blue");
+///         }
+///         Event::San { ply, san_plus } => {
+///             assert_eq!((ply, san_plus.to_string()), (0, "e4".to_owned()));
+///         }
+///         Event::Comment(comment) => {
+///             assert_eq!(comment.trim().as_bytes(), b"best by test");
+///         }
+///         _ => (),
+///     }
+/// }
+/// # Ok::<_, std::io::Error>(())
+/// ```
+///
+/// [`Event`]: enum.Event.html
+#[derive(Debug)]
+#[must_use]
+pub struct Events<'a> {
+    reader: SliceReader<'a>,
+    parser: EventParser,
+}
+
+impl<'a> Events<'a> {
+    /// Create a pull parser over the given bytes.
+    pub fn new(pgn: &'a [u8]) -> Events<'a> {
+        Events {
+            reader: SliceReader::new(pgn),
+            parser: EventParser::new(),
+        }
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub fn bytes_consumed(&self) -> usize {
+        self.reader.consumed()
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = io::Result<Event<'a>>;
+
+    fn next(&mut self) -> Option<io::Result<Event<'a>>> {
+        match self.parser.next(&mut self.reader) {
+            Ok(Some(raw)) => Some(Ok(borrow_event(raw, self.reader.remaining_bytes()))),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Returns a pull parser over the remaining games, as an alternative to
+    /// [`read_game()`](struct.BufferedReader.html#method.read_game) with a
+    /// [`Visitor`](trait.Visitor.html).
+    ///
+    /// ```
+    /// use pgn_reader::{BufferedReader, OwnedEvent};
+    ///
+    /// let mut reader = BufferedReader::new_cursor(&b"1. e4 e5 *"[..]);
+    ///
+    /// let mut moves = 0;
+    /// for event in reader.events() {
+    ///     if let OwnedEvent::San { .. } = event? {
+    ///         moves += 1;
+    ///     }
+    /// }
+    /// assert_eq!(moves, 2);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    pub fn events(&mut self) -> OwnedEvents<'_, R> {
+        OwnedEvents::new(self)
+    }
+}
+
+/// A pull parser over a [`BufferedReader`], yielding [`OwnedEvent`]s.
+/// Returned by
+/// [`BufferedReader::events()`](struct.BufferedReader.html#method.events).
+///
+/// Games read this way count towards
+/// [`BufferedReader::games_read()`](struct.BufferedReader.html#method.games_read)
+/// and progress reports. Limits set with
+/// [`BufferedReader::set_limits()`](struct.BufferedReader.html#method.set_limits)
+/// do not apply.
+///
+/// [`BufferedReader`]: struct.BufferedReader.html
+/// [`OwnedEvent`]: enum.OwnedEvent.html
+#[derive(Debug)]
+#[must_use]
+pub struct OwnedEvents<'a, R> {
+    reader: &'a mut BufferedReader<R>,
+    parser: EventParser,
+    start: u64,
+}
+
+impl<'a, R: Read> OwnedEvents<'a, R> {
+    fn new(reader: &'a mut BufferedReader<R>) -> OwnedEvents<'a, R> {
+        OwnedEvents {
+            reader,
+            parser: EventParser::new(),
+            start: 0,
+        }
+    }
+
+    /// Read the next event, borrowing from the internal buffer of the
+    /// reader rather than copying. Returns `Ok(None)` at the end of the
+    /// source.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors. Parsing continues with the next game.
+    pub fn next_event(&mut self) -> io::Result<Option<Event<'_>>> {
+        match self.parser.next(self.reader) {
+            Ok(Some(raw)) => {
+                match raw {
+                    RawEvent::BeginGame => self.start = self.reader.bytes_consumed(),
+                    RawEvent::EndGame => self.reader.game_done(true),
+                    _ => (),
+                }
+                Ok(Some(borrow_event(raw, self.reader.buffered())))
+            }
+            Ok(None) => {
+                self.reader.game_done(false);
+                Ok(None)
+            }
+            Err(err) => Err(self.reader.damaged(self.start, err)),
+        }
+    }
+}
+
+impl<'a, R: Read> Iterator for OwnedEvents<'a, R> {
+    type Item = io::Result<OwnedEvent>;
+
+    fn next(&mut self) -> Option<io::Result<OwnedEvent>> {
+        match self.next_event() {
+            Ok(Some(event)) => Some(Ok(OwnedEvent::from(event))),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(pgn: &[u8]) -> Vec<OwnedEvent> {
+        Events::new(pgn)
+            .map(|event| event.map(OwnedEvent::from))
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_slice_and_buffered_agree() {
+        let pgn = b"\xef\xbb\xbf[Event \"A\"]\n[Site \"?\"]\n\n1. e4 $1 {c} (1. d4 (1. c4) 1... d5 *\n\n1. Nf3 )\n";
+
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let buffered = reader.events().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(buffered, events(pgn));
+        assert_eq!(reader.games_read(), 2);
+
+        use OwnedEvent::*;
+        assert_eq!(
+            buffered,
+            [
+                BeginGame,
+                Header {
+                    key: b"Event".to_vec(),
+                    value: b"A".to_vec()
+                },
+                Header {
+                    key: b"Site".to_vec(),
+                    value: b"?".to_vec()
+                },
+                EndHeaders,
+                San {
+                    ply: 0,
+                    san_plus: "e4".parse().unwrap()
+                },
+                Nag(crate::Nag::GOOD_MOVE),
+                Comment(b"c".to_vec()),
+                BeginVariation { depth: 1, ply: 0 },
+                San {
+                    ply: 0,
+                    san_plus: "d4".parse().unwrap()
+                },
+                BeginVariation { depth: 2, ply: 0 },
+                San {
+                    ply: 0,
+                    san_plus: "c4".parse().unwrap()
+                },
+                EndVariation,
+                San {
+                    ply: 1,
+                    san_plus: "d5".parse().unwrap()
+                },
+                Outcome(None),
+                EndVariation,
+                EndGame,
+                BeginGame,
+                EndHeaders,
+                San {
+                    ply: 0,
+                    san_plus: "Nf3".parse().unwrap()
+                },
+                EndGame,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error() {
+        let mut events = Events::new(b"1. e4 *\n\n1. d4 { unterminated");
+        let results: Vec<_> = events.by_ref().collect();
+        assert!(results[..results.len() - 1].iter().all(|e| e.is_ok()));
+        assert!(results.last().unwrap().is_err());
+        assert!(events.next().is_none());
+    }
+}
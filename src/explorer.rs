@@ -0,0 +1,654 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use shakmaty::{
+    fen::Fen,
+    san::{San, SanPlus},
+    CastlingMode, Chess, Color, Outcome, Position,
+};
+
+use crate::{
+    index::{invalid_index, read_u64},
+    reader::BufferedReader,
+    search::{hash, Replay},
+    types::{RawHeader, Skip},
+    visitor::Visitor,
+};
+
+const MAGIC: &[u8; 8] = b"PGNPOS\0\x03";
+
+/// Size of the magic, the number of positions and the ply limit.
+const HEADER_LEN: u64 = 24;
+
+/// Size of a key and offset in the sorted key table.
+const SLOT_LEN: u64 = 16;
+
+/// Number of games won by White, drawn, and won by Black.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct OutcomeStats {
+    /// Games won by White.
+    pub white: u64,
+    /// Drawn games.
+    pub draws: u64,
+    /// Games won by Black.
+    pub black: u64,
+}
+
+impl OutcomeStats {
+    /// Returns the number of games with a known outcome.
+    pub fn total(&self) -> u64 {
+        self.white + self.draws + self.black
+    }
+
+    /// Counts a game with the given outcome. Unknown outcomes are ignored.
+    pub fn add(&mut self, outcome: Option<Outcome>) {
+        match outcome {
+            Some(Outcome::Decisive {
+                winner: Color::White,
+            }) => self.white += 1,
+            Some(Outcome::Decisive {
+                winner: Color::Black,
+            }) => self.black += 1,
+            Some(Outcome::Draw) => self.draws += 1,
+            None => (),
+        }
+    }
+}
+
+/// A move played from an indexed position.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MoveEntry {
+    /// The move, in normalized SAN.
+    pub san: San,
+    /// Outcomes of the games in which the move was played.
+    pub stats: OutcomeStats,
+}
+
+/// Everything known about an indexed position.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct PositionEntry {
+    /// Outcomes of the games that reached the position.
+    pub stats: OutcomeStats,
+    /// Byte offsets of the games that reached the position, in order.
+    pub games: Vec<u64>,
+    /// The moves played from the position, in the order they were first
+    /// seen.
+    pub moves: Vec<MoveEntry>,
+}
+
+/// An opening explorer: maps positions to the games that reached them and
+/// the moves played next, with White/Draw/Black statistics.
+///
+/// Positions are keyed by Zobrist hash, so transpositions are merged.
+/// Only the mainlines of standard chess games are indexed. Games are
+/// counted once per position, even if they repeat it.
+///
+/// The index is built in memory. Saved indexes can also be queried
+/// directly from disk with [`PositionIndexFile`](struct.PositionIndexFile.html).
+///
+/// ```
+/// use pgn_reader::{BufferedReader, PositionIndex};
+///
+/// let pgn = b"1. e4 e5 1-0\n\n1. e4 c5 0-1\n\n1. d4 d5 1/2-1/2\n\n1. e4 e5 *\n";
+///
+/// let mut index = PositionIndex::new();
+/// index.add_games(&mut BufferedReader::new_cursor(&pgn[..]))?;
+///
+/// let start = index
+///     .get_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+///     .unwrap();
+/// assert_eq!(start.games.len(), 4);
+/// assert_eq!(start.stats.total(), 3);
+///
+/// let e4 = &start.moves[0];
+/// assert_eq!(e4.san.to_string(), "e4");
+/// assert_eq!((e4.stats.white, e4.stats.draws, e4.stats.black), (1, 0, 1));
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PositionIndex {
+    positions: HashMap<u64, PositionEntry>,
+    max_plies: usize,
+}
+
+impl Default for PositionIndex {
+    fn default() -> PositionIndex {
+        PositionIndex {
+            positions: HashMap::new(),
+            max_plies: usize::MAX,
+        }
+    }
+}
+
+impl PositionIndex {
+    /// Create an empty index.
+    pub fn new() -> PositionIndex {
+        PositionIndex::default()
+    }
+
+    /// Index only the first `plies` plies of each game. Unlimited by
+    /// default.
+    ///
+    /// Opening explorers usually limit the depth, because the number of
+    /// distinct positions grows with the number of plies.
+    pub fn set_max_plies(&mut self, plies: usize) {
+        self.max_plies = plies;
+    }
+
+    /// Returns the number of distinct positions.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns whether no positions have been indexed.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the entry for a position.
+    pub fn get<P: Position>(&self, pos: &P) -> Option<&PositionEntry> {
+        self.positions.get(&hash(pos))
+    }
+
+    /// Returns the entry for a position given as FEN, or `None` if the
+    /// position is not indexed or the FEN is invalid.
+    pub fn get_fen(&self, fen: &str) -> Option<&PositionEntry> {
+        let pos: Chess = fen
+            .parse::<Fen>()
+            .ok()?
+            .into_position(CastlingMode::Chess960)
+            .ok()?;
+        self.get(&pos)
+    }
+
+    /// Indexes all remaining games of `reader` in a single streaming pass.
+    /// Returns the number of games.
+    ///
+    /// The outcome of each game is taken from the `Result` header, or from
+    /// the result at the end of the movetext.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors. Games before the error remain in the
+    ///   index.
+    pub fn add_games<R: Read>(&mut self, reader: &mut BufferedReader<R>) -> io::Result<u64> {
+        let mut builder = Builder {
+            max_plies: self.max_plies,
+            replay: Replay::new(),
+            outcome: None,
+            path: Vec::new(),
+        };
+
+        let mut games = 0;
+        while reader.has_more()? {
+            let offset = reader.bytes_consumed();
+            if let Some((outcome, path)) = reader.read_game(&mut builder)? {
+                self.add_game(offset, outcome, &path);
+            }
+            games += 1;
+        }
+        Ok(games)
+    }
+
+    fn add_game(&mut self, offset: u64, outcome: Option<Outcome>, path: &[(u64, Option<San>)]) {
+        for (key, san) in path {
+            let entry = self.positions.entry(*key).or_default();
+            if entry.games.last() == Some(&offset) {
+                continue;
+            }
+            entry.games.push(offset);
+            entry.stats.add(outcome);
+            if let Some(san) = san {
+                match entry.moves.iter_mut().find(|m| m.san == *san) {
+                    Some(m) => m.stats.add(outcome),
+                    None => {
+                        let mut stats = OutcomeStats::default();
+                        stats.add(outcome);
+                        entry.moves.push(MoveEntry { san: *san, stats });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the index in a compact binary format.
+    ///
+    /// The positions are stored in a table sorted by key, followed by the
+    /// entries, so that single positions can be looked up with
+    /// [`PositionIndexFile`](struct.PositionIndexFile.html) without
+    /// loading the whole index. The ply limit is stored as well.
+    ///
+    /// # Errors
+    ///
+    /// I/O error from `writer`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.positions.len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.max_plies as u64).to_le_bytes())?;
+
+        let mut keys: Vec<_> = self.positions.keys().copied().collect();
+        keys.sort_unstable();
+
+        let mut offset = HEADER_LEN + SLOT_LEN * keys.len() as u64;
+        for key in &keys {
+            writer.write_all(&key.to_le_bytes())?;
+            writer.write_all(&offset.to_le_bytes())?;
+            offset += entry_len(&self.positions[key]);
+        }
+
+        for key in &keys {
+            write_entry(&mut writer, &self.positions[key])?;
+        }
+
+        writer.flush()
+    }
+
+    /// Reads an index previously written with
+    /// [`write_to()`](struct.PositionIndex.html#method.write_to) into
+    /// memory.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from `reader`.
+    /// * `InvalidData` if the index is corrupted or was written by an
+    ///   incompatible version.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<PositionIndex> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_index());
+        }
+
+        let len = read_u64(&mut reader)?;
+        let max_plies = read_max_plies(&mut reader)?;
+
+        // Do not trust the length for preallocation.
+        let mut keys = Vec::with_capacity(len.min(1 << 16) as usize);
+        for _ in 0..len {
+            keys.push(read_u64(&mut reader)?);
+            read_u64(&mut reader)?; // offset
+        }
+
+        // Entries follow in the order of the key table.
+        let mut index = PositionIndex::new();
+        index.max_plies = max_plies;
+        for key in keys {
+            index.positions.insert(key, read_entry(&mut reader)?);
+        }
+
+        Ok(index)
+    }
+
+    /// Saves the index to a file.
+    ///
+    /// # Errors
+    ///
+    /// I/O error when creating or writing the file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    /// Opens an index file previously written with
+    /// [`save()`](struct.PositionIndex.html#method.save).
+    ///
+    /// # Errors
+    ///
+    /// See [`read_from()`](struct.PositionIndex.html#method.read_from).
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<PositionIndex> {
+        PositionIndex::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+/// A position index file, queried without loading it into memory.
+///
+/// Each lookup is a binary search in the sorted key table of the file,
+/// followed by reading a single entry.
+///
+/// ```
+/// use std::io::Cursor;
+/// use pgn_reader::{BufferedReader, PositionIndex, PositionIndexFile};
+///
+/// let pgn = b"1. e4 e5 1-0\n\n1. d4 d5 0-1\n";
+///
+/// let mut index = PositionIndex::new();
+/// index.add_games(&mut BufferedReader::new_cursor(&pgn[..]))?;
+/// let mut bytes = Vec::new();
+/// index.write_to(&mut bytes)?;
+///
+/// let mut file = PositionIndexFile::new(Cursor::new(bytes))?;
+/// let entry = file
+///     .get_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")?
+///     .unwrap();
+/// assert_eq!(entry.games, [0]);
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct PositionIndexFile<R> {
+    inner: R,
+    len: u64,
+    max_plies: usize,
+}
+
+impl PositionIndexFile<BufReader<File>> {
+    /// Opens an index file previously written with
+    /// [`PositionIndex::save()`](struct.PositionIndex.html#method.save).
+    ///
+    /// # Errors
+    ///
+    /// See [`new()`](struct.PositionIndexFile.html#method.new).
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<PositionIndexFile<BufReader<File>>> {
+        PositionIndexFile::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> PositionIndexFile<R> {
+    /// Create a reader for an index written with
+    /// [`PositionIndex::write_to()`](struct.PositionIndex.html#method.write_to).
+    /// Only the header is read.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from `inner`.
+    /// * `InvalidData` if the file was written by an incompatible version.
+    pub fn new(mut inner: R) -> io::Result<PositionIndexFile<R>> {
+        inner.seek(SeekFrom::Start(0))?;
+        let mut magic = [0; 8];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_index());
+        }
+        let len = read_u64(&mut inner)?;
+        let max_plies = read_max_plies(&mut inner)?;
+        Ok(PositionIndexFile {
+            inner,
+            len,
+            max_plies,
+        })
+    }
+
+    /// Returns the number of distinct positions.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether no positions are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of plies indexed per game, as set with
+    /// [`PositionIndex::set_max_plies()`](struct.PositionIndex.html#method.set_max_plies).
+    pub fn max_plies(&self) -> usize {
+        self.max_plies
+    }
+
+    /// Reads the entry for a position.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * `InvalidData` if the index is corrupted.
+    pub fn get<P: Position>(&mut self, pos: &P) -> io::Result<Option<PositionEntry>> {
+        let key = hash(pos);
+
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            self.inner
+                .seek(SeekFrom::Start(HEADER_LEN + SLOT_LEN * mid))?;
+            let found = read_u64(&mut self.inner)?;
+            if found < key {
+                lo = mid + 1;
+            } else if found > key {
+                hi = mid;
+            } else {
+                let offset = read_u64(&mut self.inner)?;
+                if offset < HEADER_LEN + SLOT_LEN * self.len {
+                    return Err(invalid_index());
+                }
+                self.inner.seek(SeekFrom::Start(offset))?;
+                return read_entry(&mut self.inner).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the entry for a position given as FEN. Returns `None` if the
+    /// position is not indexed or the FEN is invalid.
+    ///
+    /// # Errors
+    ///
+    /// See [`get()`](struct.PositionIndexFile.html#method.get).
+    pub fn get_fen(&mut self, fen: &str) -> io::Result<Option<PositionEntry>> {
+        match fen
+            .parse::<Fen>()
+            .ok()
+            .and_then(|fen| fen.into_position::<Chess>(CastlingMode::Chess960).ok())
+        {
+            Some(pos) => self.get(&pos),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+fn entry_len(entry: &PositionEntry) -> u64 {
+    let moves: u64 = entry
+        .moves
+        .iter()
+        .map(|m| 1 + m.san.to_string().len() as u64 + 24)
+        .sum();
+    24 + 8 + 8 * entry.games.len() as u64 + 4 + moves
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: &PositionEntry) -> io::Result<()> {
+    write_stats(writer, &entry.stats)?;
+    writer.write_all(&(entry.games.len() as u64).to_le_bytes())?;
+    for offset in &entry.games {
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    writer.write_all(&(entry.moves.len() as u32).to_le_bytes())?;
+    for m in &entry.moves {
+        let san = m.san.to_string();
+        writer.write_all(&[san.len() as u8])?;
+        writer.write_all(san.as_bytes())?;
+        write_stats(writer, &m.stats)?;
+    }
+    Ok(())
+}
+
+fn read_entry<R: Read>(reader: &mut R) -> io::Result<PositionEntry> {
+    let mut entry = PositionEntry {
+        stats: read_stats(reader)?,
+        ..PositionEntry::default()
+    };
+    for _ in 0..read_u64(reader)? {
+        entry.games.push(read_u64(reader)?);
+    }
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    for _ in 0..u32::from_le_bytes(len) {
+        let mut len = [0];
+        reader.read_exact(&mut len)?;
+        let mut san = vec![0; usize::from(len[0])];
+        reader.read_exact(&mut san)?;
+        entry.moves.push(MoveEntry {
+            san: San::from_ascii(&san).map_err(|_| invalid_index())?,
+            stats: read_stats(reader)?,
+        });
+    }
+    Ok(entry)
+}
+
+fn write_stats<W: Write>(writer: &mut W, stats: &OutcomeStats) -> io::Result<()> {
+    writer.write_all(&stats.white.to_le_bytes())?;
+    writer.write_all(&stats.draws.to_le_bytes())?;
+    writer.write_all(&stats.black.to_le_bytes())
+}
+
+fn read_stats<R: Read>(reader: &mut R) -> io::Result<OutcomeStats> {
+    Ok(OutcomeStats {
+        white: read_u64(reader)?,
+        draws: read_u64(reader)?,
+        black: read_u64(reader)?,
+    })
+}
+
+fn read_max_plies<R: Read>(reader: &mut R) -> io::Result<usize> {
+    // Unlimited is stored as u64::MAX, which saturates on narrower targets.
+    Ok(usize::try_from(read_u64(reader)?).unwrap_or(usize::MAX))
+}
+
+/// Collects the positions of a game, with the move played next in each.
+struct Builder {
+    max_plies: usize,
+    replay: Replay,
+    outcome: Option<Outcome>,
+    path: Vec<(u64, Option<San>)>,
+}
+
+impl Builder {
+    fn push(&mut self) {
+        if !self.replay.done && self.replay.ply <= self.max_plies {
+            self.path.push((hash(&self.replay.pos), None));
+        }
+    }
+}
+
+impl Visitor for Builder {
+    type Result = (Option<Outcome>, Vec<(u64, Option<San>)>);
+
+    fn begin_game(&mut self) {
+        self.replay = Replay::new();
+        self.outcome = None;
+        self.path.clear();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        if key == b"Result" {
+            self.outcome = Outcome::from_ascii(value.as_bytes()).ok();
+        }
+        self.replay.header(key, value);
+    }
+
+    fn end_headers(&mut self) -> Skip {
+        self.push();
+        Skip(self.replay.done)
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        if self.replay.ply >= self.max_plies {
+            self.replay.done = true;
+        }
+        if self.replay.done {
+            return;
+        }
+        let before = self.replay.pos.clone();
+        if let Some(m) = self.replay.play(&san_plus) {
+            if let Some(last) = self.path.last_mut() {
+                last.1 = Some(San::from_move(&before, m));
+            }
+            self.push();
+        }
+    }
+
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+        Skip(true) // stay in the mainline
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        if self.outcome.is_none() {
+            self.outcome = outcome;
+        }
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        (self.outcome, std::mem::take(&mut self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_index() -> io::Result<()> {
+        let pgn = b"[Result \"1-0\"]\n\n1. Nf3 Nf6 2. d4 d5 3. Ng1 Ng8 4. Nf3 Nf6 1-0\n\n1. d4 d5 2. Nf3 Nf6 (2... c5) 3. c4 0-1\n\n1. e4 e5 2. Nf3 *\n";
+
+        let mut index = PositionIndex::new();
+        index.set_max_plies(4);
+        assert_eq!(
+            index.add_games(&mut BufferedReader::new_cursor(&pgn[..]))?,
+            3
+        );
+
+        // Transposition.
+        let pos = index
+            .get_fen("rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 2 3")
+            .unwrap();
+        assert_eq!(pos.games, [0, 63]);
+        assert_eq!(
+            pos.stats,
+            OutcomeStats {
+                white: 1,
+                draws: 0,
+                black: 1
+            }
+        );
+        // Beyond the maximum number of plies.
+        assert!(pos.moves.is_empty());
+
+        let start = index.get(&Chess::default()).unwrap();
+        let moves: Vec<_> = start
+            .moves
+            .iter()
+            .map(|m| (m.san.to_string(), m.stats.total()))
+            .collect();
+        assert_eq!(
+            moves,
+            [
+                ("Nf3".to_owned(), 1),
+                ("d4".to_owned(), 1),
+                ("e4".to_owned(), 0)
+            ]
+        );
+
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes)?;
+        let reopened = PositionIndex::read_from(&bytes[..])?;
+        assert_eq!(reopened, index);
+
+        let mut file = PositionIndexFile::new(io::Cursor::new(bytes))?;
+        assert_eq!(file.len(), index.len() as u64);
+        assert_eq!(file.max_plies(), 4);
+        assert_eq!(file.get(&Chess::default())?.as_ref(), Some(start));
+        assert_eq!(file.get_fen("8/8/8/8/8/8/8/K6k w - - 0 1")?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,594 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{cmp::Ordering, error::Error, fmt, str::FromStr};
+
+use crate::types::{Date, Eco, RawHeader, Skip};
+
+/// A comparison operator.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    /// Reads an operator from the start of the input. Returns its length.
+    pub(crate) fn lex(input: &[u8]) -> Option<(usize, Op)> {
+        Some(match *input {
+            [b'!', b'=', ..] => (2, Op::Ne),
+            [b'<', b'=', ..] => (2, Op::Le),
+            [b'>', b'=', ..] => (2, Op::Ge),
+            [b'=', b'=', ..] => (2, Op::Eq),
+            [b'=', ..] => (1, Op::Eq),
+            [b'<', ..] => (1, Op::Lt),
+            [b'>', ..] => (1, Op::Gt),
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A typed literal. The type is determined by the header key.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Value {
+    Text(Vec<u8>),
+    Int(i64),
+    Date(Date),
+    Eco(Eco),
+}
+
+impl Value {
+    fn parse(key: &[u8], literal: &[u8]) -> Option<Value> {
+        if key.eq_ignore_ascii_case(b"ECO") {
+            Eco::from_ascii(literal).ok().map(Value::Eco)
+        } else if key.ends_with(b"Date") {
+            Date::from_ascii(literal)
+                .ok()
+                .filter(|date| date.year.is_some())
+                .map(Value::Date)
+        } else if key.ends_with(b"Elo") || key == b"PlyCount" {
+            btoi::btoi(literal).ok().map(Value::Int)
+        } else {
+            Some(Value::Text(literal.to_vec()))
+        }
+    }
+
+    /// Compares a header value with the literal, or returns `None` if the
+    /// header value is not of the same type.
+    fn compare(&self, header: &[u8]) -> Option<Ordering> {
+        match *self {
+            Value::Text(ref text) => Some(header.cmp(text)),
+            Value::Int(n) => btoi::btoi::<i64>(header).ok().map(|h| h.cmp(&n)),
+            Value::Eco(eco) => Eco::from_ascii(header).ok().map(|h| h.cmp(&eco)),
+            Value::Date(date) => Date::from_ascii(header)
+                .ok()
+                .filter(|h| h.year.is_some())
+                .map(|h| compare_dates(h, date)),
+        }
+    }
+}
+
+/// Compares dates only as far as both are known, so that `2019.??.??`
+/// equals `2019.05.01`, and `2021.05.01` equals `2021`.
+pub(crate) fn compare_dates(a: Date, b: Date) -> Ordering {
+    [
+        (a.year, b.year),
+        (a.month.map(u16::from), b.month.map(u16::from)),
+        (a.day.map(u16::from), b.day.map(u16::from)),
+    ]
+    .iter()
+    .map_while(|&pair| match pair {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => None,
+    })
+    .find(|&ordering| ordering != Ordering::Equal)
+    .unwrap_or(Ordering::Equal)
+}
+
+/// Matches a glob with `*` and `?` wildcards, ignoring ASCII case.
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&ch) if ch == b'?' || ch.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    backtrack = Some((bp, bt + 1));
+                    p = bp + 1;
+                    t = bt + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == b'*')
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        key: Vec<u8>,
+        op: Op,
+        value: Value,
+    },
+    Range {
+        key: Vec<u8>,
+        min: Value,
+        max: Value,
+    },
+}
+
+impl Expr {
+    fn eval<'a, F>(&self, get: &mut F) -> bool
+    where
+        F: FnMut(&[u8]) -> Option<RawHeader<'a>>,
+    {
+        match *self {
+            Expr::And(ref a, ref b) => a.eval(get) && b.eval(get),
+            Expr::Or(ref a, ref b) => a.eval(get) || b.eval(get),
+            Expr::Not(ref a) => !a.eval(get),
+            Expr::Compare {
+                ref key,
+                op,
+                ref value,
+            } => get(key).is_some_and(|header| {
+                let header = header.decode();
+                match (op, value) {
+                    (Op::Eq, Value::Text(pattern)) => glob(pattern, &header),
+                    (Op::Ne, Value::Text(pattern)) => !glob(pattern, &header),
+                    _ => value.compare(&header).is_some_and(|o| op.test(o)),
+                }
+            }),
+            Expr::Range {
+                ref key,
+                ref min,
+                ref max,
+            } => get(key).is_some_and(|header| {
+                let header = header.decode();
+                min.compare(&header).is_some_and(|o| Op::Ge.test(o))
+                    && max.compare(&header).is_some_and(|o| Op::Le.test(o))
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    DotDot,
+    Op(Op),
+    Word(Vec<u8>),
+    Quoted(Vec<u8>),
+}
+
+fn is_word_char(ch: u8) -> bool {
+    ch.is_ascii_alphanumeric() || b"_.?*+-/:".contains(&ch)
+}
+
+/// Limit for nested parentheses and `not`, to bound the recursion.
+const MAX_DEPTH: usize = 100;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    peeked: Option<(usize, Token)>,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, offset: usize, reason: &'static str) -> InvalidFilter {
+        InvalidFilter { offset, reason }
+    }
+
+    fn lex(&mut self) -> Result<Option<(usize, Token)>, InvalidFilter> {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let rest = &self.input[start..];
+        let (len, token) = match *rest {
+            [] => return Ok(None),
+            [b'(', ..] => (1, Token::LParen),
+            [b')', ..] => (1, Token::RParen),
+            [b'.', b'.', ..] => (2, Token::DotDot),
+            [b'!' | b'<' | b'>' | b'=', ..] => match Op::lex(rest) {
+                Some((len, op)) => (len, Token::Op(op)),
+                None => return Err(self.error(start, "unexpected character")),
+            },
+            [b'"', ..] => {
+                let mut text = Vec::new();
+                let mut i = 1;
+                loop {
+                    match rest.get(i) {
+                        None => return Err(self.error(start, "unterminated string")),
+                        Some(b'"') => break,
+                        Some(b'\\') if i + 1 < rest.len() => {
+                            text.push(rest[i + 1]);
+                            i += 2;
+                        }
+                        Some(&ch) => {
+                            text.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                (i + 1, Token::Quoted(text))
+            }
+            [ch, ..] if is_word_char(ch) => {
+                // Stop before .. so that ranges like B20..B99 need no spaces.
+                let len = (0..rest.len())
+                    .find(|&i| !is_word_char(rest[i]) || rest[i..].starts_with(b".."))
+                    .unwrap_or(rest.len());
+                (len, Token::Word(rest[..len].to_vec()))
+            }
+            _ => return Err(self.error(start, "unexpected character")),
+        };
+        self.pos += len;
+        Ok(Some((start, token)))
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, InvalidFilter> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex()?;
+        }
+        Ok(self.peeked.as_ref().map(|(_, token)| token))
+    }
+
+    fn next(&mut self) -> Result<(usize, Option<Token>), InvalidFilter> {
+        self.peek()?;
+        Ok(match self.peeked.take() {
+            Some((offset, token)) => (offset, Some(token)),
+            None => (self.pos, None),
+        })
+    }
+
+    fn offset(&mut self) -> Result<usize, InvalidFilter> {
+        self.peek()?;
+        Ok(self.peeked.as_ref().map_or(self.pos, |&(offset, _)| offset))
+    }
+
+    fn enter(&mut self, offset: usize) -> Result<(), InvalidFilter> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(offset, "nested too deeply"));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<bool, InvalidFilter> {
+        let found = matches!(
+            self.peek()?,
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword.as_bytes())
+        );
+        if found {
+            self.next()?;
+        }
+        Ok(found)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, InvalidFilter> {
+        let mut expr = self.parse_and()?;
+        while self.keyword("or")? {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, InvalidFilter> {
+        let mut expr = self.parse_not()?;
+        while self.keyword("and")? {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, InvalidFilter> {
+        let offset = self.offset()?;
+        if self.keyword("not")? {
+            self.enter(offset)?;
+            let expr = self.parse_not()?;
+            self.depth -= 1;
+            Ok(Expr::Not(Box::new(expr)))
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, InvalidFilter> {
+        match self.next()? {
+            (offset, Some(Token::LParen)) => {
+                self.enter(offset)?;
+                let expr = self.parse_or()?;
+                self.depth -= 1;
+                match self.next()? {
+                    (_, Some(Token::RParen)) => Ok(expr),
+                    (offset, _) => Err(self.error(offset, "expected )")),
+                }
+            }
+            (_, Some(Token::Word(key))) => {
+                if self.keyword("in")? {
+                    let min = self.parse_value(&key)?;
+                    match self.next()? {
+                        (_, Some(Token::DotDot)) => (),
+                        (offset, _) => return Err(self.error(offset, "expected ..")),
+                    }
+                    let max = self.parse_value(&key)?;
+                    Ok(Expr::Range { key, min, max })
+                } else {
+                    let op = match self.next()? {
+                        (_, Some(Token::Op(op))) => op,
+                        (offset, _) => return Err(self.error(offset, "expected operator or in")),
+                    };
+                    let value = self.parse_value(&key)?;
+                    Ok(Expr::Compare { key, op, value })
+                }
+            }
+            (offset, _) => Err(self.error(offset, "expected header key or (")),
+        }
+    }
+
+    fn parse_value(&mut self, key: &[u8]) -> Result<Value, InvalidFilter> {
+        match self.next()? {
+            (offset, Some(Token::Word(literal) | Token::Quoted(literal))) => {
+                Value::parse(key, &literal)
+                    .ok_or_else(|| self.error(offset, "invalid value for key"))
+            }
+            (offset, _) => Err(self.error(offset, "expected value")),
+        }
+    }
+}
+
+/// A compiled predicate over the headers of a game.
+///
+/// Filters are written like
+/// `White = "Carlsen*" and WhiteElo > 2700 and Date >= 2019.01.01 and ECO in B20..B99`.
+///
+/// * Comparisons are `key op value` with `=`, `!=`, `<`, `<=`, `>` and
+///   `>=`, or `key in min..max` for inclusive ranges.
+/// * Comparisons can be combined with `and`, `or`, `not` and parentheses.
+/// * Values can be quoted, with `\"` and `\\` as escapes.
+///
+/// Values are typed by key:
+///
+/// * `ECO` compares ECO codes like `B20`.
+/// * Keys ending in `Date` compare dates like `2019.01.01`. Dates are
+///   compared only as far as both sides are known, so `Date <= 2021`
+///   includes all of 2021, and `2019.??.??` equals `2019.05.01`.
+/// * Keys ending in `Elo`, and `PlyCount`, compare integers.
+/// * Other keys compare text bytewise. `=` and `!=` match globs with `*`
+///   and `?` wildcards, ignoring ASCII case.
+///
+/// A comparison is false if the header is missing or its value is not of
+/// the expected type.
+///
+/// # Examples
+///
+/// Use a filter to skip games in
+/// [`Visitor::end_headers()`](trait.Visitor.html#method.end_headers):
+///
+/// ```
+/// use pgn_reader::{BufferedReader, Filter, Headers, Skip, Visitor, RawHeader};
+///
+/// struct Matching {
+///     filter: Filter,
+///     headers: Headers,
+///     count: usize,
+/// }
+///
+/// impl Visitor for Matching {
+///     type Result = ();
+///
+///     fn begin_headers(&mut self) {
+///         self.headers.clear();
+///     }
+///
+///     fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+///         self.headers.header(key, value);
+///     }
+///
+///     fn end_headers(&mut self) -> Skip {
+///         let skip = self.filter.skip(|key| self.headers.get(key));
+///         if !skip.0 {
+///             self.count += 1;
+///         }
+///         skip
+///     }
+///
+///     fn end_game(&mut self) {}
+/// }
+///
+/// let pgn = b"[White \"Carlsen, Magnus\"]\n[WhiteElo \"2863\"]\n[ECO \"B33\"]\n\n1. e4 c5 *\n\n[White \"Carlsen, Magnus\"]\n[WhiteElo \"2863\"]\n[ECO \"C65\"]\n\n1. e4 e5 *\n";
+///
+/// let mut visitor = Matching {
+///     filter: "White = \"carlsen*\" and WhiteElo > 2700 and ECO in B20..B99".parse()?,
+///     headers: Headers::new(),
+///     count: 0,
+/// };
+/// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+/// reader.read_all(&mut visitor)?;
+/// assert_eq!(visitor.count, 1);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Compiles a filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidFilter`] error for syntax errors, for values
+    /// that do not match the type of their key, like `WhiteElo > strong`,
+    /// and for parentheses or `not` nested more than 100 levels deep.
+    ///
+    /// [`InvalidFilter`]: struct.InvalidFilter.html
+    pub fn parse(filter: &str) -> Result<Filter, InvalidFilter> {
+        let mut parser = Parser {
+            input: filter.as_bytes(),
+            pos: 0,
+            peeked: None,
+            depth: 0,
+        };
+        let expr = parser.parse_or()?;
+        match parser.next()? {
+            (_, None) => Ok(Filter { expr }),
+            (offset, Some(_)) => Err(parser.error(offset, "expected and, or or end")),
+        }
+    }
+
+    /// Tests the headers of a game. `get` returns the value of the header
+    /// with the given key, if any.
+    pub fn matches<'a, F>(&self, mut get: F) -> bool
+    where
+        F: FnMut(&[u8]) -> Option<RawHeader<'a>>,
+    {
+        self.expr.eval(&mut get)
+    }
+
+    /// Like [`matches()`](struct.Filter.html#method.matches), but returns
+    /// whether to skip the game, for use in
+    /// [`Visitor::end_headers()`](trait.Visitor.html#method.end_headers).
+    pub fn skip<'a, F>(&self, get: F) -> Skip
+    where
+        F: FnMut(&[u8]) -> Option<RawHeader<'a>>,
+    {
+        Skip(!self.matches(get))
+    }
+}
+
+impl FromStr for Filter {
+    type Err = InvalidFilter;
+
+    fn from_str(s: &str) -> Result<Filter, InvalidFilter> {
+        Filter::parse(s)
+    }
+}
+
+/// Error when compiling an invalid [`Filter`](struct.Filter.html).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct InvalidFilter {
+    offset: usize,
+    reason: &'static str,
+}
+
+impl InvalidFilter {
+    /// Returns the byte offset of the error in the filter.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for InvalidFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid filter at offset {}: {}",
+            self.offset, self.reason
+        )
+    }
+}
+
+impl Error for InvalidFilter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(filter: &str, headers: &[(&str, &str)]) -> bool {
+        let filter = Filter::parse(filter).unwrap();
+        filter.matches(|key| {
+            headers
+                .iter()
+                .find(|(k, _)| k.as_bytes() == key)
+                .map(|(_, v)| RawHeader(v.as_bytes()))
+        })
+    }
+
+    #[test]
+    fn test_filter() {
+        let game = [
+            ("White", "Carlsen, Magnus"),
+            ("Black", "Caruana, Fabiano"),
+            ("WhiteElo", "2835"),
+            ("Date", "2019.??.??"),
+            ("ECO", "B33"),
+            ("Result", "1/2-1/2"),
+        ];
+
+        assert!(matches(
+            "White = \"carlsen*\" and WhiteElo > 2700 and Date >= 2019.01.01 and ECO in B20..B99",
+            &game
+        ));
+        assert!(matches(
+            "Date in 2018 .. 2019 and not (Result = 1-0 or Result = 0-1)",
+            &game
+        ));
+        assert!(matches("Black = *fab?ano and White != Caruana*", &game));
+        assert!(matches("BlackElo < 2000 or WhiteElo == 2835", &game));
+        assert!(!matches("BlackElo < 2000", &game));
+        assert!(!matches("Date < 2019.05.01", &game));
+        assert!(!matches("ECO > B33 or ECO < B33", &game));
+        assert!(!matches("Event = *", &game));
+        assert!(!matches("White = Carlsen and Black = Caruana*", &game));
+    }
+
+    #[test]
+    fn test_invalid_filter() {
+        let offset = |filter: &str| Filter::parse(filter).unwrap_err().offset();
+        assert_eq!(offset("WhiteElo > strong"), 11);
+        assert_eq!(offset("ECO in B20"), 10);
+        assert_eq!(offset("(White = \"x\""), 12);
+        assert_eq!(offset("White = x Black = y"), 10);
+        assert_eq!(offset("White = \"x"), 8);
+        assert_eq!(offset("White ~ x"), 6);
+        assert_eq!(offset(&"(".repeat(100_000)), MAX_DEPTH);
+        assert_eq!(offset(&"not ".repeat(100_000)), 4 * MAX_DEPTH);
+        assert!(Filter::parse(&format!("{}White = x{}", "(".repeat(50), ")".repeat(50))).is_ok());
+    }
+}
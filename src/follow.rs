@@ -0,0 +1,416 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt,
+    io::{self, Read},
+    thread,
+    time::Duration,
+};
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    reader::SliceReader,
+    types::{Nag, RawComment, RawHeader, Skip},
+    visitor::Visitor,
+};
+
+const READ_SIZE: usize = 64 * 1024;
+
+/// Decides how to wait for more data when a followed source is exhausted.
+pub trait Wait {
+    /// Called whenever the end of the source has been reached without a
+    /// complete game. Returns `false` to stop following.
+    ///
+    /// # Errors
+    ///
+    /// Errors are passed on to the caller of
+    /// [`Follow::read_game()`](struct.Follow.html#method.read_game).
+    fn wait(&mut self) -> io::Result<bool>;
+}
+
+/// Waits by sleeping for a fixed duration, and follows forever.
+#[derive(Clone, Debug)]
+pub struct Sleep(pub Duration);
+
+impl Wait for Sleep {
+    fn wait(&mut self) -> io::Result<bool> {
+        thread::sleep(self.0);
+        Ok(true)
+    }
+}
+
+impl<F: FnMut() -> io::Result<bool>> Wait for F {
+    fn wait(&mut self) -> io::Result<bool> {
+        self()
+    }
+}
+
+/// A game produced by [`Follow::read_game()`](struct.Follow.html#method.read_game).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Followed<T> {
+    /// A complete game. It will not be reported again.
+    Complete(T),
+    /// A snapshot of the game that is currently being written. It will be
+    /// reported again when it grows, and finally as complete.
+    InProgress(T),
+}
+
+impl<T> Followed<T> {
+    /// Returns the result produced by the visitor.
+    pub fn into_inner(self) -> T {
+        match self {
+            Followed::Complete(result) | Followed::InProgress(result) => result,
+        }
+    }
+}
+
+/// Follows a growing source, like a PGN file that a broadcast relay keeps
+/// appending to, similar to `tail -f`.
+///
+/// Unlike [`BufferedReader`], the end of the source is not considered the
+/// end of the last game. A game is complete only when another game
+/// follows, or when it is terminated by a decisive result or a draw. A
+/// game terminated by `*` is considered in progress until another game
+/// follows.
+///
+/// ```
+/// # use std::io;
+/// # fn try_main() -> io::Result<()> {
+/// use std::fs::File;
+/// use pgn_reader::{Follow, Followed};
+/// # struct Relay;
+/// # impl pgn_reader::Visitor for Relay {
+/// #     type Result = ();
+/// #     fn end_game(&mut self) {}
+/// # }
+///
+/// let mut follow = Follow::new(File::open("broadcast.pgn")?);
+/// follow.set_report_in_progress(true);
+///
+/// while let Some(game) = follow.read_game(&mut Relay)? {
+///     match game {
+///         Followed::Complete(game) => { /* publish final game */ }
+///         Followed::InProgress(game) => { /* publish live update */ }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`BufferedReader`]: struct.BufferedReader.html
+pub struct Follow<R, W = Sleep> {
+    inner: R,
+    wait: W,
+    pending: Vec<u8>,
+    start: usize,
+    report_in_progress: bool,
+    reported: usize,
+}
+
+impl<R: Read> Follow<R> {
+    /// Follow `inner`, polling for more data every 500 milliseconds.
+    pub fn new(inner: R) -> Follow<R> {
+        Follow::with_wait(inner, Sleep(Duration::from_millis(500)))
+    }
+}
+
+impl<R: Read, W: Wait> Follow<R, W> {
+    /// Follow `inner`, using a custom strategy to wait for more data.
+    pub fn with_wait(inner: R, wait: W) -> Follow<R, W> {
+        Follow {
+            inner,
+            wait,
+            pending: Vec::new(),
+            start: 0,
+            report_in_progress: false,
+            reported: 0,
+        }
+    }
+
+    /// Also report snapshots of the game that is currently being written,
+    /// whenever it has grown and the end of the source has been reached.
+    /// Such snapshots always end with a call to
+    /// [`Visitor::outcome()`](trait.Visitor.html#method.outcome), with
+    /// `None` (`*`) unless the text already contains a result.
+    pub fn set_report_in_progress(&mut self, report_in_progress: bool) {
+        self.report_in_progress = report_in_progress;
+    }
+
+    /// Read the next complete game (or in progress snapshot), waiting for
+    /// more data as required. Returns `Ok(None)` if the wait strategy
+    /// decided to stop following.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader or the wait strategy.
+    /// * Irrecoverable parser errors in a complete game.
+    pub fn read_game<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> io::Result<Option<Followed<V::Result>>> {
+        loop {
+            if let Some(end) = self.complete_game_end() {
+                let game = &self.pending[self.start..self.start + end];
+                self.start += end;
+                let result = SliceReader::new(game).read_game(visitor)?;
+                self.compact();
+                if let Some(result) = result {
+                    return Ok(Some(Followed::Complete(result)));
+                }
+                continue;
+            }
+
+            if self.fill()? > 0 {
+                continue;
+            }
+
+            if self.report_in_progress && self.pending.len() > self.reported {
+                self.reported = self.pending.len();
+                let mut in_progress = InProgress {
+                    inner: visitor,
+                    outcome: false,
+                };
+                if let Ok(Some(result)) =
+                    SliceReader::new(&self.pending[self.start..]).read_game(&mut in_progress)
+                {
+                    return Ok(Some(Followed::InProgress(result)));
+                }
+            }
+
+            if !self.wait.wait()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Returns the data that has been read, but not yet delivered as part
+    /// of a complete game.
+    pub fn pending(&self) -> &[u8] {
+        &self.pending[self.start..]
+    }
+
+    /// Reads once from the underlying reader. Returns the number of new
+    /// bytes.
+    fn fill(&mut self) -> io::Result<usize> {
+        let len = self.pending.len();
+        self.pending.resize(len + READ_SIZE, 0);
+        let size = loop {
+            match self.inner.read(&mut self.pending[len..]) {
+                Ok(size) => break size,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.pending.truncate(len);
+                    return Err(err);
+                }
+            }
+        };
+        self.pending.truncate(len + size);
+        Ok(size)
+    }
+
+    /// Returns the length of the first pending game (including trailing
+    /// whitespace), if it is complete.
+    fn complete_game_end(&self) -> Option<usize> {
+        let mut reader = SliceReader::new(&self.pending[self.start..]);
+        match reader.skip_game() {
+            Ok(true) => (),
+            // No game yet, or the data ends in the middle of a token.
+            Ok(false) | Err(_) => return None,
+        }
+
+        let end = reader.consumed();
+        if !reader.remaining_bytes().is_empty() {
+            // Another game follows.
+            return Some(end);
+        }
+
+        let game = self.pending[self.start..self.start + end].trim_ascii_end();
+        if game.ends_with(b"1-0") || game.ends_with(b"0-1") || game.ends_with(b"1/2-1/2") {
+            Some(end)
+        } else {
+            None
+        }
+    }
+
+    fn compact(&mut self) {
+        if self.start >= READ_SIZE && self.start * 2 >= self.pending.len() {
+            self.pending.drain(..self.start);
+            self.reported = self.reported.saturating_sub(self.start);
+            self.start = 0;
+        }
+    }
+}
+
+impl<R, W> fmt::Debug for Follow<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Follow")
+            .field("pending", &(self.pending.len() - self.start))
+            .field("report_in_progress", &self.report_in_progress)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Forwards to a visitor, making sure that an outcome is reported.
+struct InProgress<'a, V> {
+    inner: &'a mut V,
+    outcome: bool,
+}
+
+impl<'a, V: Visitor> Visitor for InProgress<'a, V> {
+    type Result = V::Result;
+
+    fn begin_game(&mut self) {
+        self.inner.begin_game();
+    }
+    fn begin_headers(&mut self) {
+        self.inner.begin_headers();
+    }
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        self.inner.header(key, value);
+    }
+    fn end_headers(&mut self) -> Skip {
+        self.inner.end_headers()
+    }
+    fn san_at(&mut self, ply: usize, san_plus: SanPlus) {
+        self.inner.san_at(ply, san_plus);
+    }
+    fn nag(&mut self, nag: Nag) {
+        self.inner.nag(nag);
+    }
+    fn comment(&mut self, comment: RawComment<'_>) {
+        self.inner.comment(comment);
+    }
+    fn begin_variation(&mut self, depth: usize, ply: usize) -> Skip {
+        self.inner.begin_variation(depth, ply)
+    }
+    fn end_variation(&mut self) {
+        self.inner.end_variation();
+    }
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.outcome = true;
+        self.inner.outcome(outcome);
+    }
+    fn end_game(&mut self) -> Self::Result {
+        if !self.outcome {
+            self.inner.outcome(None);
+        }
+        self.inner.end_game()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+    use super::*;
+
+    /// A source that grows by one chunk whenever the follower waits.
+    struct Growing {
+        data: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Read for Growing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut data = self.data.borrow_mut();
+            let size = buf.len().min(data.len());
+            buf[..size].copy_from_slice(&data[..size]);
+            data.drain(..size);
+            Ok(size)
+        }
+    }
+
+    struct Moves {
+        sans: Vec<String>,
+        outcome: Option<Option<Outcome>>,
+    }
+
+    impl Visitor for Moves {
+        type Result = (Vec<String>, Option<Option<Outcome>>);
+
+        fn san(&mut self, san_plus: SanPlus) {
+            self.sans.push(san_plus.to_string());
+        }
+
+        fn outcome(&mut self, outcome: Option<Outcome>) {
+            self.outcome = Some(outcome);
+        }
+
+        fn end_game(&mut self) -> Self::Result {
+            (std::mem::take(&mut self.sans), self.outcome.take())
+        }
+    }
+
+    #[test]
+    fn test_follow() -> io::Result<()> {
+        let data = Rc::new(RefCell::new(Vec::new()));
+        let mut chunks: VecDeque<&[u8]> = VecDeque::from([
+            &b"[Event \"Live\"]\n\n1. e4 e"[..],
+            &b"5 *"[..],
+            &b"\n\n[Event \"Next\"]\n\n1. d4 d5 2. c4 1"[..],
+            &b"-0\n"[..],
+        ]);
+
+        let source = Growing { data: data.clone() };
+        let wait = {
+            let data = data.clone();
+            move || {
+                Ok(match chunks.pop_front() {
+                    Some(chunk) => {
+                        data.borrow_mut().extend_from_slice(chunk);
+                        true
+                    }
+                    None => false,
+                })
+            }
+        };
+
+        let mut follow = Follow::with_wait(source, wait);
+        follow.set_report_in_progress(true);
+        let mut visitor = Moves {
+            sans: Vec::new(),
+            outcome: None,
+        };
+
+        let mut games = Vec::new();
+        while let Some(game) = follow.read_game(&mut visitor)? {
+            games.push(game);
+        }
+
+        let white = Some(Outcome::Decisive {
+            winner: shakmaty::Color::White,
+        });
+        assert_eq!(
+            games,
+            [
+                Followed::InProgress((vec!["e4".to_owned()], Some(None))),
+                Followed::InProgress((vec!["e4".to_owned(), "e5".to_owned()], Some(None))),
+                Followed::Complete((vec!["e4".to_owned(), "e5".to_owned()], Some(None))),
+                Followed::InProgress((
+                    vec!["d4".to_owned(), "d5".to_owned(), "c4".to_owned()],
+                    Some(None)
+                )),
+                Followed::Complete((
+                    vec!["d4".to_owned(), "d5".to_owned(), "c4".to_owned()],
+                    Some(white)
+                )),
+            ]
+        );
+        assert!(follow.pending().is_empty());
+        Ok(())
+    }
+}
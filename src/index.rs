@@ -0,0 +1,513 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::Range,
+    path::Path,
+};
+
+use shakmaty::{Color, Outcome};
+
+use crate::{
+    reader::{BufferedReader, Checkpoint},
+    scan::{HeaderRow, HeaderTable},
+    types::{Date, Eco},
+};
+
+const MAGIC: &[u8; 8] = b"PGNIDX\0\x01";
+
+/// The indexed headers of a single game.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct IndexEntry {
+    /// Byte span of the game in the PGN file.
+    pub span: Range<u64>,
+    /// The `White` header.
+    pub white: String,
+    /// The `Black` header.
+    pub black: String,
+    /// The `Event` header.
+    pub event: String,
+    /// The `Date` header, if valid.
+    pub date: Option<Date>,
+    /// The `Result` header, if decisive or drawn.
+    pub result: Option<Outcome>,
+    /// The `ECO` header, if valid.
+    pub eco: Option<Eco>,
+    /// The `WhiteElo` header, if valid and not zero.
+    pub white_elo: Option<u16>,
+    /// The `BlackElo` header, if valid and not zero.
+    pub black_elo: Option<u16>,
+}
+
+impl IndexEntry {
+    fn from_row(row: &HeaderRow<'_>) -> IndexEntry {
+        let text = |key: &[u8]| {
+            row.get(key)
+                .map_or_else(String::new, |value| value.decode_utf8_lossy().into_owned())
+        };
+        let parse = |key: &[u8]| row.get(key).map(|value| value.decode());
+        let elo = |key: &[u8]| {
+            parse(key)
+                .and_then(|value| btoi::btou::<u16>(&value).ok())
+                .filter(|&elo| elo > 0)
+        };
+        IndexEntry {
+            span: row.span(),
+            white: text(b"White"),
+            black: text(b"Black"),
+            event: text(b"Event"),
+            date: parse(b"Date")
+                .and_then(|value| Date::from_ascii(&value).ok())
+                .filter(is_known),
+            result: parse(b"Result").and_then(|value| Outcome::from_ascii(&value).ok()),
+            eco: parse(b"ECO").and_then(|value| Eco::from_ascii(&value).ok()),
+            white_elo: elo(b"WhiteElo"),
+            black_elo: elo(b"BlackElo"),
+        }
+    }
+}
+
+/// Criteria for
+/// [`HeaderIndex::query()`](struct.HeaderIndex.html#method.query).
+///
+/// All given criteria must match. Names are matched as ASCII
+/// case-insensitive substrings. Ranges are inclusive, and games with
+/// missing or invalid values never match a range.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Query {
+    /// Either player.
+    pub player: Option<String>,
+    /// The player with the white pieces.
+    pub white: Option<String>,
+    /// The player with the black pieces.
+    pub black: Option<String>,
+    /// The event.
+    pub event: Option<String>,
+    /// Earliest date.
+    pub min_date: Option<Date>,
+    /// Latest date.
+    pub max_date: Option<Date>,
+    /// Exact result.
+    pub result: Option<Outcome>,
+    /// Lowest ECO code.
+    pub min_eco: Option<Eco>,
+    /// Highest ECO code.
+    pub max_eco: Option<Eco>,
+    /// Lowest rating of both players.
+    pub min_elo: Option<u16>,
+    /// Highest rating of both players.
+    pub max_elo: Option<u16>,
+}
+
+impl Query {
+    /// Create a query that matches all games.
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    /// Tests if the entry matches all criteria.
+    pub fn matches(&self, entry: &IndexEntry) -> bool {
+        fn contains(haystack: &str, needle: &Option<String>) -> bool {
+            match needle {
+                None => true,
+                Some(needle) => {
+                    needle.is_empty()
+                        || haystack
+                            .as_bytes()
+                            .windows(needle.len())
+                            .any(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+                }
+            }
+        }
+
+        fn within<T: Ord>(value: Option<T>, min: Option<T>, max: Option<T>) -> bool {
+            match value {
+                _ if min.is_none() && max.is_none() => true,
+                None => false,
+                Some(ref value) => {
+                    !matches!(min, Some(ref min) if min > value)
+                        && !matches!(max, Some(ref max) if max < value)
+                }
+            }
+        }
+
+        contains(&entry.white, &self.white)
+            && contains(&entry.black, &self.black)
+            && contains(&entry.event, &self.event)
+            && (contains(&entry.white, &self.player) || contains(&entry.black, &self.player))
+            && (self.result.is_none() || entry.result == self.result)
+            && within(entry.date, self.min_date, self.max_date)
+            && within(entry.eco, self.min_eco, self.max_eco)
+            && within(entry.white_elo, self.min_elo, self.max_elo)
+            && within(entry.black_elo, self.min_elo, self.max_elo)
+    }
+}
+
+/// A persistent index of the most important headers of a PGN file, with
+/// the byte span of each game.
+///
+/// The index is built once with
+/// [`append_from()`](struct.HeaderIndex.html#method.append_from), saved
+/// to disk, and can then be reopened and queried without touching the PGN
+/// text. When games are appended to the PGN file, the index can be brought
+/// up to date with [`update()`](struct.HeaderIndex.html#method.update).
+///
+/// ```
+/// use pgn_reader::{BufferedReader, HeaderIndex, Query};
+///
+/// let pgn = b"[White \"Carlsen, Magnus\"]\n[WhiteElo \"2863\"]\n[Result \"1-0\"]\n\n1. e4 1-0\n\n[White \"Anand, Viswanathan\"]\n\n1. d4 *\n";
+///
+/// let mut index = HeaderIndex::new();
+/// index.append_from(&mut BufferedReader::new_cursor(&pgn[..]))?;
+///
+/// let mut bytes = Vec::new();
+/// index.write_to(&mut bytes)?;
+/// let index = HeaderIndex::read_from(&bytes[..])?;
+///
+/// let query = Query {
+///     player: Some("carlsen".to_owned()),
+///     ..Query::default()
+/// };
+/// let games: Vec<_> = index.query(&query).map(|(i, entry)| (i, entry.white_elo)).collect();
+/// assert_eq!(games, [(0, Some(2863))]);
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct HeaderIndex {
+    entries: Vec<IndexEntry>,
+    checkpoint: Checkpoint,
+}
+
+impl HeaderIndex {
+    /// Create an empty index.
+    pub fn new() -> HeaderIndex {
+        HeaderIndex::default()
+    }
+
+    /// Returns the number of indexed games.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no indexed games.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entry of the game with the given index.
+    pub fn get(&self, index: usize) -> Option<&IndexEntry> {
+        self.entries.get(index)
+    }
+
+    /// Iterates over all entries.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &IndexEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the position up to which the PGN file has been indexed.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.checkpoint
+    }
+
+    /// Returns the games matching the query, with their indexes.
+    pub fn query<'a>(
+        &'a self,
+        query: &'a Query,
+    ) -> impl Iterator<Item = (usize, &'a IndexEntry)> + 'a {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, entry)| query.matches(entry))
+    }
+
+    /// Indexes all remaining games of `reader`. Returns the number of games
+    /// added.
+    ///
+    /// The reader should start where the index left off, like a reader
+    /// created with [`BufferedReader::resume()`] from
+    /// [`checkpoint()`](struct.HeaderIndex.html#method.checkpoint).
+    ///
+    /// # Errors
+    ///
+    /// See [`BufferedReader::scan_headers()`]. Games before the error
+    /// remain in the index.
+    ///
+    /// [`BufferedReader::resume()`]: struct.BufferedReader.html#method.resume
+    /// [`BufferedReader::scan_headers()`]: struct.BufferedReader.html#method.scan_headers
+    pub fn append_from<R: Read>(&mut self, reader: &mut BufferedReader<R>) -> io::Result<u64> {
+        let mut table = HeaderTable::new();
+        let result = reader.scan_headers(&mut table);
+        self.entries
+            .extend(table.iter().map(|row| IndexEntry::from_row(&row)));
+        self.checkpoint = reader.checkpoint();
+        result
+    }
+
+    /// Indexes the games that have been appended to the PGN file at `path`
+    /// since the index was last updated. Returns the number of games added.
+    ///
+    /// Games that are already indexed are assumed to be unchanged.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error when reading the file.
+    /// * `InvalidData` if the file is shorter than the indexed part, so
+    ///   that it cannot be the same file.
+    /// * Irrecoverable parser errors, see
+    ///   [`append_from()`](struct.HeaderIndex.html#method.append_from).
+    pub fn update<P: AsRef<Path>>(&mut self, path: P) -> io::Result<u64> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() < self.checkpoint.offset {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "pgn file is shorter than indexed",
+            ));
+        }
+        self.append_from(&mut BufferedReader::resume(file, self.checkpoint)?)
+    }
+
+    /// Writes the index in a compact binary format.
+    ///
+    /// # Errors
+    ///
+    /// I/O error from `writer`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.checkpoint.offset.to_le_bytes())?;
+        writer.write_all(&self.checkpoint.games.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+
+        for entry in &self.entries {
+            writer.write_all(&entry.span.start.to_le_bytes())?;
+            writer.write_all(&entry.span.end.to_le_bytes())?;
+
+            let date = entry.date.unwrap_or(Date {
+                year: None,
+                month: None,
+                day: None,
+            });
+            writer.write_all(&date.year.unwrap_or(u16::MAX).to_le_bytes())?;
+            writer.write_all(&[date.month.unwrap_or(0), date.day.unwrap_or(0)])?;
+            writer.write_all(&[match entry.result {
+                None => 0,
+                Some(Outcome::Decisive {
+                    winner: Color::White,
+                }) => 1,
+                Some(Outcome::Decisive {
+                    winner: Color::Black,
+                }) => 2,
+                Some(Outcome::Draw) => 3,
+            }])?;
+            writer.write_all(&entry.eco.map_or([0, 0], |eco| [eco.volume, eco.number]))?;
+            writer.write_all(&entry.white_elo.unwrap_or(0).to_le_bytes())?;
+            writer.write_all(&entry.black_elo.unwrap_or(0).to_le_bytes())?;
+
+            for text in [&entry.white, &entry.black, &entry.event] {
+                writer.write_all(&(text.len() as u32).to_le_bytes())?;
+                writer.write_all(text.as_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Reads an index previously written with
+    /// [`write_to()`](struct.HeaderIndex.html#method.write_to).
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from `reader`.
+    /// * `InvalidData` if the index is corrupted or was written by an
+    ///   incompatible version.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<HeaderIndex> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_index());
+        }
+
+        let checkpoint = Checkpoint {
+            offset: read_u64(&mut reader)?,
+            games: read_u64(&mut reader)?,
+        };
+        let len = read_u64(&mut reader)?;
+
+        // Do not trust the length for preallocation.
+        let mut entries = Vec::with_capacity(len.min(1 << 16) as usize);
+        for _ in 0..len {
+            let span = read_u64(&mut reader)?..read_u64(&mut reader)?;
+
+            let mut fixed = [0; 11];
+            reader.read_exact(&mut fixed)?;
+            let year = u16::from_le_bytes([fixed[0], fixed[1]]);
+            let date = Date {
+                year: Some(year).filter(|&year| year != u16::MAX),
+                month: Some(fixed[2]).filter(|&month| month != 0),
+                day: Some(fixed[3]).filter(|&day| day != 0),
+            };
+            let result = match fixed[4] {
+                0 => None,
+                1 => Some(Outcome::Decisive {
+                    winner: Color::White,
+                }),
+                2 => Some(Outcome::Decisive {
+                    winner: Color::Black,
+                }),
+                3 => Some(Outcome::Draw),
+                _ => return Err(invalid_index()),
+            };
+            let eco = match fixed[5] {
+                0 => None,
+                volume => Some(Eco {
+                    volume,
+                    number: fixed[6],
+                }),
+            };
+            let elo = |lo, hi| Some(u16::from_le_bytes([lo, hi])).filter(|&elo| elo != 0);
+
+            entries.push(IndexEntry {
+                span,
+                date: Some(date).filter(is_known),
+                result,
+                eco,
+                white_elo: elo(fixed[7], fixed[8]),
+                black_elo: elo(fixed[9], fixed[10]),
+                white: read_string(&mut reader)?,
+                black: read_string(&mut reader)?,
+                event: read_string(&mut reader)?,
+            });
+        }
+
+        Ok(HeaderIndex {
+            entries,
+            checkpoint,
+        })
+    }
+
+    /// Saves the index to a file.
+    ///
+    /// # Errors
+    ///
+    /// I/O error when creating or writing the file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    /// Opens an index file previously written with
+    /// [`save()`](struct.HeaderIndex.html#method.save).
+    ///
+    /// # Errors
+    ///
+    /// See [`read_from()`](struct.HeaderIndex.html#method.read_from).
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<HeaderIndex> {
+        HeaderIndex::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+fn is_known(date: &Date) -> bool {
+    date.year.is_some() || date.month.is_some() || date.day.is_some()
+}
+
+pub(crate) fn invalid_index() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid index file")
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let mut buf = Vec::new();
+    reader
+        .take(u64::from(u32::from_le_bytes(len)))
+        .read_to_end(&mut buf)?;
+    if buf.len() != u32::from_le_bytes(len) as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(buf).map_err(|_| invalid_index())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_index() -> io::Result<()> {
+        let mut pgn = b"[White \"Carlsen, Magnus\"]\n[Black \"Caruana, Fabiano\"]\n[Date \"2019.03.??\"]\n[Result \"1/2-1/2\"]\n[ECO \"B33\"]\n[WhiteElo \"2845\"]\n[BlackElo \"2828\"]\n\n1. e4 c5 1/2-1/2\n\n".to_vec();
+
+        let mut index = HeaderIndex::new();
+        assert_eq!(index.append_from(&mut BufferedReader::new_cursor(&pgn))?, 1);
+
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes)?;
+        assert_eq!(HeaderIndex::read_from(&bytes[..])?, index);
+        assert!(HeaderIndex::read_from(&bytes[..bytes.len() - 1]).is_err());
+
+        pgn.extend_from_slice(b"[Event \"Tata Steel\"]\n[White \"Anand, V.\"]\n[Date \"2008.??.??\"]\n[ECO \"E15\"]\n[Result \"0-1\"]\n\n1. d4 0-1\n");
+        let mut reader = BufferedReader::resume(Cursor::new(&pgn), index.checkpoint())?;
+        assert_eq!(index.append_from(&mut reader)?, 1);
+        assert_eq!(index.checkpoint().games, 2);
+        assert_eq!(index.get(1).unwrap().span.end, pgn.len() as u64);
+
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes)?;
+        let index = HeaderIndex::read_from(&bytes[..])?;
+
+        let matching = |query: &Query| index.query(query).map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(matching(&Query::new()), [0, 1]);
+        assert_eq!(
+            matching(&Query {
+                player: Some("CARUANA".to_owned()),
+                ..Query::default()
+            }),
+            [0]
+        );
+        assert_eq!(
+            matching(&Query {
+                min_date: Some("2010.01.01".parse().unwrap()),
+                ..Query::default()
+            }),
+            [0]
+        );
+        assert_eq!(
+            matching(&Query {
+                min_eco: Some("C00".parse().unwrap()),
+                max_eco: Some("E99".parse().unwrap()),
+                result: Some(Outcome::Decisive {
+                    winner: Color::Black
+                }),
+                ..Query::default()
+            }),
+            [1]
+        );
+        assert_eq!(
+            matching(&Query {
+                min_elo: Some(2830),
+                ..Query::default()
+            }),
+            Vec::<usize>::new()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    mem,
+};
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use shakmaty::{san::SanPlus, uci::UciMove, Outcome};
+
+use crate::{
+    reader::BufferedReader,
+    search::Replay,
+    types::{Nag, RawComment, RawHeader, Skip},
+    visitor::Visitor,
+};
+
+/// A game in a form suitable for JSON, as produced by
+/// [`JsonExporter`](struct.JsonExporter.html).
+#[derive(Clone, PartialEq, Debug, Default, Serialize)]
+pub struct JsonGame {
+    /// Decoded headers, in the order of the game. Serialized as an object.
+    #[serde(serialize_with = "serialize_headers")]
+    pub headers: Vec<(String, String)>,
+    /// Comments before the first move.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<JsonComment>,
+    /// The mainline.
+    pub moves: Vec<JsonMove>,
+    /// The game termination, like `1-0` or `*`. Taken from the `Result`
+    /// header if the movetext has no termination marker.
+    pub result: String,
+}
+
+/// A move with its annotations.
+#[derive(Clone, PartialEq, Debug, Default, Serialize)]
+pub struct JsonMove {
+    /// The move as written, like `Nf3+`.
+    pub san: String,
+    /// The move in UCI notation, like `g1f3`. `None` after the first illegal
+    /// move of a line, or for variants.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uci: Option<String>,
+    /// Numeric annotation glyphs.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nags: Vec<Nag>,
+    /// Comments after the move. Comments at the start of a variation are
+    /// attached to its first move.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<JsonComment>,
+    /// Alternatives to this move, each a list of moves.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<Vec<JsonMove>>,
+}
+
+/// A comment, with embedded commands like `[%clk 0:03:00]` and
+/// `[%eval 0.17]` parsed.
+///
+/// ```
+/// use pgn_reader::{Eval, JsonComment, RawComment};
+///
+/// let comment = JsonComment::from_raw(&RawComment(b" [%eval #-3] [%clk 1:02:03.5] Oops "));
+/// assert_eq!(comment.text, "Oops");
+/// assert_eq!(comment.clock, Some(3723.5));
+/// assert_eq!(comment.eval, Some(Eval::Mate(-3)));
+/// ```
+#[derive(Clone, PartialEq, Debug, Default, Serialize)]
+pub struct JsonComment {
+    /// The text without commands, with whitespace normalized.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    /// Remaining clock time in seconds, from `[%clk]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock: Option<f64>,
+    /// Engine evaluation, from `[%eval]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval: Option<Eval>,
+}
+
+/// An engine evaluation from White's point of view. Serialized as
+/// `{"cp": 17}` or `{"mate": -3}`.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Eval {
+    /// Centipawns.
+    Cp(i32),
+    /// Moves to mate, negative if Black mates.
+    Mate(i32),
+}
+
+impl JsonComment {
+    /// Decodes a comment and extracts its commands. Unknown commands are
+    /// kept in the text.
+    pub fn from_raw(comment: &RawComment<'_>) -> JsonComment {
+        let decoded = comment.decode_utf8_lossy();
+        let mut comment = JsonComment::default();
+        let mut rest = decoded.as_ref();
+        let mut text = String::new();
+        while let Some(start) = rest.find("[%") {
+            let end = match rest[start..].find(']') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let command = &rest[start + 2..end];
+            let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+            let parsed = match name {
+                "clk" => parse_clock(args.trim()).map(|clock| comment.clock = Some(clock)),
+                "eval" => parse_eval(args.trim()).map(|eval| comment.eval = Some(eval)),
+                _ => None,
+            };
+            text.push_str(&rest[..start]);
+            if parsed.is_none() {
+                text.push_str(&rest[start..=end]);
+            }
+            text.push(' ');
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        comment.text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        comment
+    }
+}
+
+fn parse_clock(s: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in s.split(':') {
+        let value: f64 = part.parse().ok()?;
+        if !value.is_finite() || value < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
+
+fn parse_eval(s: &str) -> Option<Eval> {
+    // Ignore the optional search depth, like in [%eval 0.17,20].
+    let s = s.split(',').next()?;
+    match s.strip_prefix('#') {
+        Some(mate) => mate.parse().ok().map(Eval::Mate),
+        None => {
+            let pawns: f64 = s.parse().ok()?;
+            pawns
+                .is_finite()
+                .then(|| Eval::Cp((pawns * 100.0).round() as i32))
+        }
+    }
+}
+
+fn serialize_headers<S: Serializer>(
+    headers: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(headers.len()))?;
+    for (key, value) in headers {
+        map.serialize_entry(key, value)?;
+    }
+    map.end()
+}
+
+struct Line {
+    moves: Vec<JsonMove>,
+    comments: Vec<JsonComment>,
+    before: Replay,
+    replay: Replay,
+}
+
+impl Line {
+    fn new(replay: Replay) -> Line {
+        Line {
+            moves: Vec::new(),
+            comments: Vec::new(),
+            before: replay.clone(),
+            replay,
+        }
+    }
+}
+
+/// A [`Visitor`](trait.Visitor.html) that converts games to
+/// [`JsonGame`](struct.JsonGame.html).
+///
+/// Moves are replayed to provide UCI notation, also in variations.
+///
+/// ```
+/// use pgn_reader::{BufferedReader, JsonExporter};
+///
+/// let pgn = b"[White \"Carlsen, Magnus\"]\n\n1. e4 $1 { [%clk 0:03:00] } (1. d4) 1-0";
+///
+/// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+/// let game = reader.read_game(&mut JsonExporter::new())?.unwrap();
+///
+/// assert_eq!(
+///     serde_json::to_string(&game)?,
+///     r#"{"headers":{"White":"Carlsen, Magnus"},"moves":[{"san":"e4","uci":"e2e4","nags":[1],"comments":[{"clock":180.0}],"variations":[[{"san":"d4","uci":"d2d4"}]]}],"result":"1-0"}"#
+/// );
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub struct JsonExporter {
+    game: JsonGame,
+    lines: Vec<Line>,
+}
+
+impl JsonExporter {
+    /// Create a new exporter.
+    pub fn new() -> JsonExporter {
+        JsonExporter {
+            game: JsonGame::default(),
+            lines: vec![Line::new(Replay::new())],
+        }
+    }
+}
+
+impl Default for JsonExporter {
+    fn default() -> JsonExporter {
+        JsonExporter::new()
+    }
+}
+
+impl fmt::Debug for JsonExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonExporter")
+            .field("game", &self.game)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Visitor for JsonExporter {
+    type Result = JsonGame;
+
+    fn begin_game(&mut self) {
+        *self = JsonExporter::new();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        if key == b"Result" {
+            // Used if the movetext has no termination marker.
+            if let Ok(outcome) = Outcome::from_ascii(value.as_bytes()) {
+                self.game.result = outcome.to_string();
+            }
+        }
+        self.lines[0].replay.header(key, value.clone());
+        self.game.headers.push((
+            String::from_utf8_lossy(key).into_owned(),
+            value.decode_utf8_lossy().into_owned(),
+        ));
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        let line = self.lines.last_mut().expect("mainline");
+        line.before = line.replay.clone();
+        let castling_mode = line.replay.castling_mode;
+        let uci = line
+            .replay
+            .play(&san_plus)
+            .map(|m| UciMove::from_move(m, castling_mode).to_string());
+        line.moves.push(JsonMove {
+            san: san_plus.to_string(),
+            uci,
+            comments: mem::take(&mut line.comments),
+            ..JsonMove::default()
+        });
+    }
+
+    fn nag(&mut self, nag: Nag) {
+        if let Some(last) = self.lines.last_mut().and_then(|line| line.moves.last_mut()) {
+            last.nags.push(nag);
+        }
+    }
+
+    fn comment(&mut self, comment: RawComment<'_>) {
+        let comment = JsonComment::from_raw(&comment);
+        let mainline = self.lines.len() == 1;
+        let line = self.lines.last_mut().expect("mainline");
+        match line.moves.last_mut() {
+            Some(last) => last.comments.push(comment),
+            None if mainline => self.game.comments.push(comment),
+            None => line.comments.push(comment),
+        }
+    }
+
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+        let parent = self.lines.last().expect("mainline");
+        let replay = if parent.moves.is_empty() {
+            parent.replay.clone()
+        } else {
+            parent.before.clone()
+        };
+        self.lines.push(Line::new(replay));
+        Skip(false)
+    }
+
+    fn end_variation(&mut self) {
+        if self.lines.len() > 1 {
+            let variation = self.lines.pop().expect("variation");
+            let parent = self.lines.last_mut().expect("mainline");
+            if let Some(last) = parent.moves.last_mut() {
+                if !variation.moves.is_empty() {
+                    last.variations.push(variation.moves);
+                }
+            }
+        }
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.game.result = match outcome {
+            Some(outcome) => outcome.to_string(),
+            None => "*".to_owned(),
+        };
+    }
+
+    fn end_game(&mut self) -> JsonGame {
+        while self.lines.len() > 1 {
+            self.end_variation();
+        }
+        let mut game = mem::take(&mut self.game);
+        game.moves = mem::take(&mut self.lines[0].moves);
+        if game.result.is_empty() {
+            game.result.push('*');
+        }
+        game
+    }
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Writes all remaining games to `writer` as newline delimited JSON,
+    /// one [`JsonGame`](struct.JsonGame.html) per line. Returns the number
+    /// of games.
+    ///
+    /// ```
+    /// use pgn_reader::BufferedReader;
+    ///
+    /// let pgn = b"1. e4 e5 *\n\n[Event \"Rated Blitz\"]\n\n1. d4 0-1\n";
+    ///
+    /// let mut ndjson = Vec::new();
+    /// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+    /// assert_eq!(reader.write_ndjson(&mut ndjson)?, 2);
+    /// assert_eq!(ndjson.iter().filter(|&&ch| ch == b'\n').count(), 2);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader or from `writer`.
+    /// * Irrecoverable parser errors. Games before the error have been
+    ///   written.
+    pub fn write_ndjson<W: Write>(&mut self, mut writer: W) -> io::Result<u64> {
+        let mut exporter = JsonExporter::new();
+        let mut games = 0;
+        while let Some(game) = self.read_game(&mut exporter)? {
+            serde_json::to_writer(&mut writer, &game)?;
+            writer.write_all(b"\n")?;
+            games += 1;
+        }
+        writer.flush()?;
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_exporter() {
+        let pgn = b"[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\"]\n\n{ start } 1. O-O-O { [%eval 0.5,12] [%foo] } ( { alt } 1. Kd1 Kd7 ( 1... Ke7 ) ) ( ) 1... Ke7 2. Qh5 Kd6";
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let game = reader.read_game(&mut JsonExporter::new()).unwrap().unwrap();
+
+        assert_eq!(game.comments[0].text, "start");
+        assert_eq!(game.result, "*");
+        assert_eq!(game.moves.len(), 4);
+
+        let castles = &game.moves[0];
+        assert_eq!(castles.uci.as_deref(), Some("e1c1"));
+        assert_eq!(castles.comments[0].text, "[%foo]");
+        assert_eq!(castles.comments[0].eval, Some(Eval::Cp(50)));
+        assert_eq!(castles.variations.len(), 1);
+
+        let variation = &castles.variations[0];
+        assert_eq!(variation[0].uci.as_deref(), Some("e1d1"));
+        assert_eq!(variation[0].comments[0].text, "alt");
+        assert_eq!(variation[1].variations[0][0].uci.as_deref(), Some("e8e7"));
+
+        assert_eq!(game.moves[1].uci.as_deref(), Some("e8e7"));
+        assert_eq!(game.moves[2].uci, None);
+        assert_eq!(game.moves[3].uci, None);
+
+        let pgn = b"[Result \"0-1\"]\n\n1. e4\n\n[Result \"0-1\"]\n\n1. d4 *";
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let game = reader.read_game(&mut JsonExporter::new()).unwrap().unwrap();
+        assert_eq!(game.result, "0-1");
+        let game = reader.read_game(&mut JsonExporter::new()).unwrap().unwrap();
+        assert_eq!(game.result, "*");
+    }
+}
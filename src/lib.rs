@@ -62,7 +62,7 @@
 //!         self.moves += 1;
 //!     }
 //!
-//!     fn begin_variation(&mut self) -> Skip {
+//!     fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
 //!         Skip(true) // stay in the mainline
 //!     }
 //!
@@ -121,7 +121,7 @@
 //!         }
 //!     }
 //!
-//!     fn begin_variation(&mut self) -> Skip {
+//!     fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
 //!         Skip(true) // stay in the mainline
 //!     }
 //!
@@ -149,20 +149,91 @@
 //! }
 //! ```
 //!
+//! # Crate features
+//!
+//! * `bzip2`, `xz`, `gzip`, `lz4`, `zstd`: Transparent decompression of
+//!   the respective format with [`BufferedReader::open_auto()`] and
+//!   [`BufferedReader::from_reader_auto()`]. `compression` enables all of
+//!   them.
+//! * `zip`, `tar`: Reading all `*.pgn` entries of archives with
+//!   [`ZipReader`] and [`TarReader`].
+//! * `serde`: Converting games to JSON with [`JsonExporter`] and
+//!   [`BufferedReader::write_ndjson()`], and `Serialize` for [`Nag`],
+//!   [`RawHeader`], [`RawComment`] and [`Skip`].
+//! * `cli`: The `pgn` command-line tool.
+//!
 //! [Shakmaty]: ../shakmaty/index.html
 
 #![doc(html_root_url = "https://docs.rs/pgn-reader/0.20.0")]
 #![forbid(unsafe_op_in_unsafe_fn)]
 #![warn(missing_debug_implementations)]
 
+#[cfg(any(feature = "zip", feature = "tar"))]
+mod archive;
+#[cfg(any(
+    feature = "bzip2",
+    feature = "xz",
+    feature = "gzip",
+    feature = "lz4",
+    feature = "zstd"
+))]
+mod compression;
+mod cst;
+mod dedup;
+mod events;
+mod explorer;
+mod filter;
+mod follow;
+mod index;
+#[cfg(feature = "serde")]
+mod json;
+mod limits;
+mod multi;
+mod pattern;
+mod progress;
 mod reader;
+mod rewrite;
+mod scan;
+mod search;
+mod split;
+mod tokenizer;
 mod types;
 mod visitor;
 
-pub use reader::{BufferedReader, IntoIter};
+#[cfg(feature = "tar")]
+pub use archive::TarReader;
+#[cfg(feature = "zip")]
+pub use archive::ZipReader;
+#[cfg(any(
+    feature = "bzip2",
+    feature = "xz",
+    feature = "gzip",
+    feature = "lz4",
+    feature = "zstd"
+))]
+pub use compression::Compression;
+pub use cst::{Document, Game, Node};
+pub use dedup::{Dedup, DuplicateCluster};
+pub use events::{Event, Events, OwnedEvent, OwnedEvents};
+pub use explorer::{MoveEntry, OutcomeStats, PositionEntry, PositionIndex, PositionIndexFile};
+pub use filter::{Filter, InvalidFilter};
+pub use follow::{Follow, Followed, Sleep, Wait};
+pub use index::{HeaderIndex, IndexEntry, Query};
+#[cfg(feature = "serde")]
+pub use json::{Eval, JsonComment, JsonExporter, JsonGame, JsonMove};
+pub use limits::{Limit, LimitAction, LimitExceeded, Limits};
+pub use multi::MultiReader;
+pub use pattern::{InvalidPattern, Pattern};
+pub use progress::{Progress, ProgressInterval};
+pub use reader::{BufferedReader, Checkpoint, DamagedGame, IntoIter, Recovery};
+pub use rewrite::Headers;
+pub use scan::{HeaderRow, HeaderTable};
+pub use search::{InvalidMaterial, Material, PositionHit, PositionSearch};
 pub use shakmaty::{
     san::{San, SanPlus},
     CastlingSide, Color, File, Outcome, Rank, Role, Square,
 };
-pub use types::{Nag, RawComment, RawHeader, Skip};
+pub use split::{OwnedRawGames, RawGames};
+pub use tokenizer::{Token, TokenKind, Tokenizer};
+pub use types::{Date, Eco, InvalidDate, InvalidEco, Nag, RawComment, RawHeader, Skip};
 pub use visitor::Visitor;
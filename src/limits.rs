@@ -0,0 +1,232 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{error::Error, fmt, io};
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    types::{Nag, RawComment, RawHeader, Skip},
+    visitor::Visitor,
+};
+
+/// Resource limits for untrusted input, enforced per game by
+/// [`BufferedReader::set_limits()`](struct.BufferedReader.html#method.set_limits).
+///
+/// ```
+/// use pgn_reader::{LimitAction, Limits};
+///
+/// let limits = Limits {
+///     max_game_bytes: Some(1 << 20),
+///     max_depth: Some(32),
+///     max_moves: Some(10_000),
+///     action: LimitAction::Reject,
+///     ..Limits::default()
+/// };
+/// ```
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Limits {
+    /// Maximum size of a game in bytes. Checked before each token, so games
+    /// are cut off at most one token, like a long comment, after the limit.
+    pub max_game_bytes: Option<u64>,
+    /// Maximum nesting depth of variations. The mainline has depth 0.
+    pub max_depth: Option<usize>,
+    /// Maximum number of headers.
+    pub max_headers: Option<usize>,
+    /// Maximum number of comments.
+    pub max_comments: Option<usize>,
+    /// Maximum number of moves, including moves in variations.
+    pub max_moves: Option<usize>,
+    /// What to do with games that exceed a limit.
+    pub action: LimitAction,
+}
+
+/// What to do with a game that exceeds one of the [`Limits`].
+///
+/// In any case, the visitor receives no further calls for the game, except
+/// for [`Visitor::end_game()`], and the rest of the game is skipped.
+///
+/// [`Limits`]: struct.Limits.html
+/// [`Visitor::end_game()`]: trait.Visitor.html#tymethod.end_game
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum LimitAction {
+    /// Return an error with a [`LimitExceeded`] payload. Reading can
+    /// continue with the next game. This is the default.
+    ///
+    /// [`LimitExceeded`]: struct.LimitExceeded.html
+    #[default]
+    Reject,
+    /// Discard the result of the game and continue with the next game.
+    Skip,
+}
+
+/// A limit from [`Limits`](struct.Limits.html).
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Limit {
+    /// [`Limits::max_game_bytes`](struct.Limits.html#structfield.max_game_bytes).
+    GameBytes,
+    /// [`Limits::max_depth`](struct.Limits.html#structfield.max_depth).
+    Depth,
+    /// [`Limits::max_headers`](struct.Limits.html#structfield.max_headers).
+    Headers,
+    /// [`Limits::max_comments`](struct.Limits.html#structfield.max_comments).
+    Comments,
+    /// [`Limits::max_moves`](struct.Limits.html#structfield.max_moves).
+    Moves,
+}
+
+/// Payload of the error returned for a game that exceeds a limit, with
+/// [`LimitAction::Reject`](enum.LimitAction.html#variant.Reject).
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LimitExceeded {
+    /// The limit that was exceeded first.
+    pub limit: Limit,
+    /// Byte offset of the start of the game.
+    pub start: u64,
+}
+
+impl LimitExceeded {
+    /// Extracts the exceeded limit from an error returned by
+    /// [`BufferedReader::read_game()`](struct.BufferedReader.html#method.read_game),
+    /// if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&LimitExceeded> {
+        err.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = match self.limit {
+            Limit::GameBytes => "game size",
+            Limit::Depth => "variation depth",
+            Limit::Headers => "number of headers",
+            Limit::Comments => "number of comments",
+            Limit::Moves => "number of moves",
+        };
+        write!(f, "{} limit exceeded by game at byte {}", limit, self.start)
+    }
+}
+
+impl Error for LimitExceeded {}
+
+fn exceeds(count: usize, max: Option<usize>) -> bool {
+    max.is_some_and(|max| count > max)
+}
+
+/// Forwards to a visitor until a limit is exceeded.
+pub(crate) struct Limited<'a, V> {
+    inner: &'a mut V,
+    limits: &'a Limits,
+    headers: usize,
+    comments: usize,
+    moves: usize,
+    in_movetext: bool,
+    exceeded: Option<Limit>,
+}
+
+impl<'a, V> Limited<'a, V> {
+    pub(crate) fn new(inner: &'a mut V, limits: &'a Limits) -> Limited<'a, V> {
+        Limited {
+            inner,
+            limits,
+            headers: 0,
+            comments: 0,
+            moves: 0,
+            in_movetext: false,
+            exceeded: None,
+        }
+    }
+
+    pub(crate) fn exceeded(&self) -> Option<Limit> {
+        self.exceeded
+    }
+
+    pub(crate) fn in_movetext(&self) -> bool {
+        self.in_movetext
+    }
+
+    fn check(&mut self, count: usize, max: Option<usize>, limit: Limit) -> bool {
+        if self.exceeded.is_none() && exceeds(count, max) {
+            self.exceeded = Some(limit);
+        }
+        self.exceeded.is_none()
+    }
+}
+
+impl<'a, V: Visitor> Visitor for Limited<'a, V> {
+    type Result = V::Result;
+
+    fn begin_source(&mut self, name: &str) {
+        self.inner.begin_source(name);
+    }
+    fn begin_game(&mut self) {
+        self.inner.begin_game();
+    }
+    fn begin_headers(&mut self) {
+        self.inner.begin_headers();
+    }
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        self.headers += 1;
+        if self.check(self.headers, self.limits.max_headers, Limit::Headers) {
+            self.inner.header(key, value);
+        }
+    }
+    fn end_headers(&mut self) -> Skip {
+        self.in_movetext = true;
+        if self.exceeded.is_none() {
+            self.inner.end_headers()
+        } else {
+            Skip(true)
+        }
+    }
+    fn san_at(&mut self, ply: usize, san_plus: SanPlus) {
+        self.moves += 1;
+        if self.check(self.moves, self.limits.max_moves, Limit::Moves) {
+            self.inner.san_at(ply, san_plus);
+        }
+    }
+    fn nag(&mut self, nag: Nag) {
+        if self.exceeded.is_none() {
+            self.inner.nag(nag);
+        }
+    }
+    fn comment(&mut self, comment: RawComment<'_>) {
+        self.comments += 1;
+        if self.check(self.comments, self.limits.max_comments, Limit::Comments) {
+            self.inner.comment(comment);
+        }
+    }
+    fn begin_variation(&mut self, depth: usize, ply: usize) -> Skip {
+        if self.check(depth, self.limits.max_depth, Limit::Depth) {
+            self.inner.begin_variation(depth, ply)
+        } else {
+            Skip(true)
+        }
+    }
+    fn end_variation(&mut self) {
+        if self.exceeded.is_none() {
+            self.inner.end_variation();
+        }
+    }
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        if self.exceeded.is_none() {
+            self.inner.outcome(outcome);
+        }
+    }
+    fn end_game(&mut self) -> Self::Result {
+        self.inner.end_game()
+    }
+}
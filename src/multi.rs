@@ -0,0 +1,271 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{reader::BufferedReader, visitor::Visitor};
+
+/// Counts the line breaks read from a source.
+struct LineCounter<R> {
+    inner: R,
+    lines: u64,
+}
+
+impl<R: Read> Read for LineCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.lines += memchr::memchr_iter(b'\n', &buf[..size]).count() as u64;
+        Ok(size)
+    }
+}
+
+struct Source<R> {
+    name: String,
+    reader: BufferedReader<LineCounter<R>>,
+}
+
+type Open<R> = Box<dyn FnOnce() -> io::Result<R>>;
+
+/// Reads games from a sequence of sources, like multiple PGN files.
+///
+/// Unlike concatenating the sources with [`Read::chain()`], there is always
+/// a game boundary between two sources, even if a source lacks the trailing
+/// blank line. The name of the current source and the position of the
+/// current game within it are available for diagnostics.
+///
+/// [`Visitor::begin_source()`] is called with the name of each source
+/// before its first game.
+///
+/// ```
+/// # use std::io;
+/// # fn try_main() -> io::Result<()> {
+/// use pgn_reader::MultiReader;
+/// # struct Validator;
+/// # impl pgn_reader::Visitor for Validator {
+/// #     type Result = bool;
+/// #     fn end_game(&mut self) -> bool { true }
+/// # }
+///
+/// let mut reader = MultiReader::open(["a.pgn", "b.pgn"]);
+///
+/// while let Some(valid) = reader.read_game(&mut Validator)? {
+///     if !valid {
+///         eprintln!("{}:{}: invalid game", reader.source_name().unwrap(), reader.game_line());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Read::chain()`]: https://doc.rust-lang.org/std/io/trait.Read.html#method.chain
+/// [`Visitor::begin_source()`]: trait.Visitor.html#method.begin_source
+pub struct MultiReader<R> {
+    pending: VecDeque<(String, Open<R>)>,
+    current: Option<Source<R>>,
+    game_offset: u64,
+    game_line: u64,
+}
+
+impl MultiReader<File> {
+    /// Create a reader over a list of files. The files are opened lazily,
+    /// when the previous file has been read completely.
+    pub fn open<I, P>(paths: I) -> MultiReader<File>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut reader = MultiReader::new();
+        for path in paths {
+            let path = path.as_ref().to_owned();
+            reader.push_with(path.display().to_string(), move || File::open(path));
+        }
+        reader
+    }
+}
+
+impl<R: Read> MultiReader<R> {
+    /// Create a reader without any sources.
+    pub fn new() -> MultiReader<R> {
+        MultiReader {
+            pending: VecDeque::new(),
+            current: None,
+            game_offset: 0,
+            game_line: 0,
+        }
+    }
+
+    /// Append a source.
+    pub fn push<S: Into<String>>(&mut self, name: S, inner: R)
+    where
+        R: 'static,
+    {
+        self.push_with(name, move || Ok(inner));
+    }
+
+    /// Append a source that is opened only when it is reached. Errors from
+    /// `open` are reported by the read that reaches the source.
+    pub fn push_with<S, F>(&mut self, name: S, open: F)
+    where
+        S: Into<String>,
+        F: FnOnce() -> io::Result<R> + 'static,
+    {
+        self.pending.push_back((name.into(), Box::new(open)));
+    }
+
+    /// Read a single game, if any, and returns the result produced by the
+    /// visitor. Returns Ok(None) if all sources are exhausted.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from opening or reading a source.
+    /// * Irrecoverable parser errors.
+    pub fn read_game<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<Option<V::Result>> {
+        loop {
+            let source = match self.current {
+                Some(ref mut source) => source,
+                None => {
+                    let (name, open) = match self.pending.pop_front() {
+                        Some(pending) => pending,
+                        None => return Ok(None),
+                    };
+                    visitor.begin_source(&name);
+                    let inner = LineCounter {
+                        inner: open()?,
+                        lines: 0,
+                    };
+                    self.game_offset = 0;
+                    self.game_line = 1;
+                    self.current.insert(Source {
+                        name,
+                        reader: BufferedReader::new(inner),
+                    })
+                }
+            };
+
+            if source.reader.has_more()? {
+                let buffered = source.reader.buffered();
+                self.game_offset = source.reader.bytes_consumed();
+                self.game_line = source.reader.get_ref().lines
+                    - memchr::memchr_iter(b'\n', buffered).count() as u64
+                    + 1;
+                return source.reader.read_game(visitor);
+            }
+
+            self.current = None;
+        }
+    }
+
+    /// Read all games from all sources.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from opening or reading a source.
+    /// * Irrecoverable parser errors.
+    pub fn read_all<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
+        while self.read_game(visitor)?.is_some() {}
+        Ok(())
+    }
+
+    /// Returns the name of the source of the most recently read game, or of
+    /// the source currently being opened or read. Returns `None` once all
+    /// sources are exhausted.
+    pub fn source_name(&self) -> Option<&str> {
+        self.current.as_ref().map(|source| source.name.as_str())
+    }
+
+    /// Returns the byte offset of the most recently read game within its
+    /// source.
+    pub fn game_offset(&self) -> u64 {
+        self.game_offset
+    }
+
+    /// Returns the line number (starting at 1) of the most recently read
+    /// game within its source.
+    pub fn game_line(&self) -> u64 {
+        self.game_line
+    }
+}
+
+impl<R: Read> Default for MultiReader<R> {
+    fn default() -> MultiReader<R> {
+        MultiReader::new()
+    }
+}
+
+impl<R> fmt::Debug for MultiReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiReader")
+            .field("source_name", &self.current.as_ref().map(|s| &s.name))
+            .field("game_offset", &self.game_offset)
+            .field("game_line", &self.game_line)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    struct GameVisitor;
+
+    impl Visitor for GameVisitor {
+        type Result = ();
+
+        fn end_game(&mut self) {}
+    }
+
+    #[test]
+    fn test_boundary_between_sources() -> io::Result<()> {
+        let mut reader = MultiReader::new();
+        reader.push(
+            "a.pgn",
+            Cursor::new(&b"\xef\xbb\xbf[Event \"A\"]\n\n1. e4 *"[..]),
+        );
+        reader.push("empty.pgn", Cursor::new(&b"\n\n"[..]));
+        reader.push(
+            "b.pgn",
+            Cursor::new(&b"[Event \"B\"]\n\n1. d4 *\n\n\n[Event \"C\"]\n1. c4 *\n"[..]),
+        );
+
+        let mut games = Vec::new();
+        while reader.read_game(&mut GameVisitor)?.is_some() {
+            games.push((
+                reader.source_name().unwrap().to_owned(),
+                reader.game_offset(),
+                reader.game_line(),
+            ));
+        }
+
+        assert_eq!(
+            games,
+            [
+                ("a.pgn".to_owned(), 3, 1),
+                ("b.pgn".to_owned(), 0, 1),
+                ("b.pgn".to_owned(), 23, 6),
+            ]
+        );
+        Ok(())
+    }
+}
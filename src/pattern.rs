@@ -0,0 +1,727 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read},
+    str::FromStr,
+};
+
+use shakmaty::{san::SanPlus, Bitboard, Board, Chess, Color, Piece, Position, Role};
+
+use crate::{
+    filter::Op,
+    reader::BufferedReader,
+    search::{hash, Material, PositionHit, Replay},
+    types::{RawHeader, Skip},
+    visitor::Visitor,
+};
+
+/// A set of pieces on a set of squares, like `N`, `Ke1` or `P[a-h]7`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Designator {
+    pieces: [bool; 12],
+    squares: u64,
+}
+
+impl Designator {
+    fn parse(word: &[u8]) -> Option<Designator> {
+        fn piece_index(ch: u8) -> Option<&'static [usize]> {
+            const INDEXES: [usize; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+            Some(match ch {
+                b'A' => &INDEXES[..6],
+                b'a' => &INDEXES[6..],
+                _ => {
+                    let piece = Piece::from_char(char::from(ch))?;
+                    let i = usize::from(piece.color.is_black()) * 6 + piece.role as usize - 1;
+                    &INDEXES[i..i + 1]
+                }
+            })
+        }
+
+        /// Parses `x`, `[xyz]` or `[x-z]` with `x` in `first..=last`.
+        fn set(input: &[u8], first: u8, last: u8) -> Option<(u64, &[u8])> {
+            let in_range = |ch: &u8| (first..=last).contains(ch);
+            let bit = |ch: u8| 1u64 << (ch - first);
+            match *input {
+                [b'[', ref rest @ ..] => {
+                    let end = rest.iter().position(|&ch| ch == b']')?;
+                    let mut mask = 0;
+                    let mut items = &rest[..end];
+                    while let Some((&ch, tail)) = items.split_first() {
+                        match *tail {
+                            [b'-', to, ref tail @ ..]
+                                if in_range(&ch) && in_range(&to) && ch <= to =>
+                            {
+                                mask |= (ch..=to).map(bit).fold(0, |a, b| a | b);
+                                items = tail;
+                            }
+                            _ if in_range(&ch) => {
+                                mask |= bit(ch);
+                                items = tail;
+                            }
+                            _ => return None,
+                        }
+                    }
+                    Some((mask, &rest[end + 1..]))
+                }
+                [ch, ref rest @ ..] if in_range(&ch) => Some((bit(ch), rest)),
+                _ => None,
+            }
+        }
+
+        let mut pieces = [false; 12];
+        let rest = match *word {
+            [b'[', ref rest @ ..] => {
+                let end = rest.iter().position(|&ch| ch == b']')?;
+                for &ch in &rest[..end] {
+                    for &i in piece_index(ch)? {
+                        pieces[i] = true;
+                    }
+                }
+                &rest[end + 1..]
+            }
+            [ch, ref rest @ ..] => {
+                for &i in piece_index(ch)? {
+                    pieces[i] = true;
+                }
+                rest
+            }
+            [] => return None,
+        };
+
+        let squares = if rest.is_empty() {
+            !0
+        } else {
+            let (files, rest) = set(rest, b'a', b'h')?;
+            let (ranks, rest) = set(rest, b'1', b'8')?;
+            if !rest.is_empty() {
+                return None;
+            }
+            (0..64)
+                .filter(|sq| files & (1 << (sq % 8)) != 0 && ranks & (1 << (sq / 8)) != 0)
+                .fold(0, |mask, sq| mask | 1 << sq)
+        };
+
+        Some(Designator { pieces, squares })
+    }
+
+    fn bitboard(&self, board: &Board) -> Bitboard {
+        let mut bitboard = Bitboard::EMPTY;
+        for (i, _) in self.pieces.iter().enumerate().filter(|(_, &on)| on) {
+            let color = if i < 6 { Color::White } else { Color::Black };
+            bitboard |= board.by_color(color) & board.by_role(ROLES[i % 6]);
+        }
+        bitboard & Bitboard(self.squares)
+    }
+}
+
+const ROLES: [Role; 6] = [
+    Role::Pawn,
+    Role::Knight,
+    Role::Bishop,
+    Role::Rook,
+    Role::Queen,
+    Role::King,
+];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Check,
+    Mate,
+    Stalemate,
+    Turn(Color),
+    Repeated(usize),
+    Ply(Op, usize),
+    Count(Designator, Op, usize),
+    Material(Material),
+    Exists(Designator),
+    Attacks(Designator, Designator),
+    Fork(Designator, Designator, Designator),
+    Behind(Designator, Designator),
+}
+
+/// The position being tested, with its history.
+struct Context<'a> {
+    pos: &'a Chess,
+    ply: usize,
+    repetitions: usize,
+}
+
+impl Expr {
+    fn eval(&self, ctx: &Context<'_>) -> bool {
+        let board = ctx.pos.board();
+        match *self {
+            Expr::And(ref a, ref b) => a.eval(ctx) && b.eval(ctx),
+            Expr::Or(ref a, ref b) => a.eval(ctx) || b.eval(ctx),
+            Expr::Not(ref a) => !a.eval(ctx),
+            Expr::Check => ctx.pos.is_check(),
+            Expr::Mate => ctx.pos.is_checkmate(),
+            Expr::Stalemate => ctx.pos.is_stalemate(),
+            Expr::Turn(color) => ctx.pos.turn() == color,
+            Expr::Repeated(n) => ctx.repetitions >= n,
+            Expr::Ply(op, n) => op.test(ctx.ply.cmp(&n)),
+            Expr::Count(ref d, op, n) => op.test(d.bitboard(board).count().cmp(&n)),
+            Expr::Material(ref material) => Material::from_board(board) == *material,
+            Expr::Exists(ref d) => d.bitboard(board).any(),
+            Expr::Attacks(ref from, ref to) => {
+                let targets = to.bitboard(board);
+                from.bitboard(board)
+                    .into_iter()
+                    .any(|sq| (board.attacks_from(sq) & targets).any())
+            }
+            Expr::Fork(ref from, ref a, ref b) => {
+                let (a, b) = (a.bitboard(board), b.bitboard(board));
+                from.bitboard(board).into_iter().any(|sq| {
+                    let attacks = board.attacks_from(sq);
+                    (attacks & a).any() && (attacks & b).any() && (attacks & (a | b)).count() >= 2
+                })
+            }
+            Expr::Behind(ref back, ref front) => {
+                let back = back.bitboard(board);
+                front.bitboard(board).into_iter().any(|sq| {
+                    let delta = match board.color_at(sq) {
+                        Some(Color::White) => -8,
+                        _ => 8,
+                    };
+                    let mut sq = sq;
+                    while let Some(next) = sq.offset(delta) {
+                        if board.occupied().contains(next) {
+                            return back.contains(next);
+                        }
+                        sq = next;
+                    }
+                    false
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Comma,
+    Op(Op),
+    Word(Vec<u8>),
+}
+
+fn is_word_char(ch: u8) -> bool {
+    ch.is_ascii_alphanumeric() || b"[]-".contains(&ch)
+}
+
+/// Limit for nested parentheses and `not`, to bound the recursion.
+const MAX_DEPTH: usize = 100;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    peeked: Option<(usize, Token)>,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, offset: usize, reason: &'static str) -> InvalidPattern {
+        InvalidPattern { offset, reason }
+    }
+
+    fn lex(&mut self) -> Result<Option<(usize, Token)>, InvalidPattern> {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let rest = &self.input[start..];
+        let (len, token) = match *rest {
+            [] => return Ok(None),
+            [b'(', ..] => (1, Token::LParen),
+            [b')', ..] => (1, Token::RParen),
+            [b',', ..] => (1, Token::Comma),
+            [ch, ..] if is_word_char(ch) => {
+                let len = rest
+                    .iter()
+                    .position(|&ch| !is_word_char(ch))
+                    .unwrap_or(rest.len());
+                (len, Token::Word(rest[..len].to_vec()))
+            }
+            _ => match Op::lex(rest) {
+                Some((len, op)) => (len, Token::Op(op)),
+                None => return Err(self.error(start, "unexpected character")),
+            },
+        };
+        self.pos += len;
+        Ok(Some((start, token)))
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, InvalidPattern> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex()?;
+        }
+        Ok(self.peeked.as_ref().map(|(_, token)| token))
+    }
+
+    fn next(&mut self) -> Result<(usize, Option<Token>), InvalidPattern> {
+        self.peek()?;
+        Ok(match self.peeked.take() {
+            Some((offset, token)) => (offset, Some(token)),
+            None => (self.pos, None),
+        })
+    }
+
+    fn offset(&mut self) -> Result<usize, InvalidPattern> {
+        self.peek()?;
+        Ok(self.peeked.as_ref().map_or(self.pos, |&(offset, _)| offset))
+    }
+
+    fn enter(&mut self, offset: usize) -> Result<(), InvalidPattern> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(offset, "nested too deeply"));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<bool, InvalidPattern> {
+        let found = matches!(self.peek()?, Some(Token::Word(word)) if word == keyword.as_bytes());
+        if found {
+            self.next()?;
+        }
+        Ok(found)
+    }
+
+    fn expect(&mut self, expected: Token, reason: &'static str) -> Result<(), InvalidPattern> {
+        match self.next()? {
+            (_, Some(token)) if token == expected => Ok(()),
+            (offset, _) => Err(self.error(offset, reason)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, InvalidPattern> {
+        let mut expr = self.parse_and()?;
+        while self.keyword("or")? {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, InvalidPattern> {
+        let mut expr = self.parse_not()?;
+        while self.keyword("and")? {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, InvalidPattern> {
+        let offset = self.offset()?;
+        if self.keyword("not")? {
+            self.enter(offset)?;
+            let expr = self.parse_not()?;
+            self.depth -= 1;
+            Ok(Expr::Not(Box::new(expr)))
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, InvalidPattern> {
+        let (offset, word) = match self.next()? {
+            (offset, Some(Token::LParen)) => {
+                self.enter(offset)?;
+                let expr = self.parse_or()?;
+                self.depth -= 1;
+                self.expect(Token::RParen, "expected )")?;
+                return Ok(expr);
+            }
+            (offset, Some(Token::Word(word))) => (offset, word),
+            (offset, _) => return Err(self.error(offset, "expected pattern")),
+        };
+
+        Ok(match &word[..] {
+            b"check" => Expr::Check,
+            b"mate" => Expr::Mate,
+            b"stalemate" => Expr::Stalemate,
+            b"wtm" => Expr::Turn(Color::White),
+            b"btm" => Expr::Turn(Color::Black),
+            b"repeated" => Expr::Repeated(self.parse_number()?),
+            b"ply" => Expr::Ply(self.parse_op()?, self.parse_number()?),
+            b"count" => {
+                let designator = self.parse_args::<1>()?[0];
+                Expr::Count(designator, self.parse_op()?, self.parse_number()?)
+            }
+            b"material" => {
+                self.expect(Token::Op(Op::Eq), "expected =")?;
+                match self.next()? {
+                    (offset, Some(Token::Word(word))) => Expr::Material(
+                        Material::from_ascii(&word)
+                            .map_err(|_| self.error(offset, "invalid material signature"))?,
+                    ),
+                    (offset, _) => return Err(self.error(offset, "expected material signature")),
+                }
+            }
+            b"attacks" => {
+                let [from, to] = self.parse_args()?;
+                Expr::Attacks(from, to)
+            }
+            b"fork" => {
+                let [from, a, b] = self.parse_args()?;
+                Expr::Fork(from, a, b)
+            }
+            b"behind" => {
+                let [back, front] = self.parse_args()?;
+                Expr::Behind(back, front)
+            }
+            _ => Expr::Exists(
+                Designator::parse(&word)
+                    .ok_or_else(|| self.error(offset, "invalid piece designator"))?,
+            ),
+        })
+    }
+
+    fn parse_designator(&mut self) -> Result<Designator, InvalidPattern> {
+        match self.next()? {
+            (offset, Some(Token::Word(word))) => Designator::parse(&word)
+                .ok_or_else(|| self.error(offset, "invalid piece designator")),
+            (offset, _) => Err(self.error(offset, "expected piece designator")),
+        }
+    }
+
+    fn parse_args<const N: usize>(&mut self) -> Result<[Designator; N], InvalidPattern> {
+        self.expect(Token::LParen, "expected (")?;
+        let mut args = [Designator {
+            pieces: [false; 12],
+            squares: 0,
+        }; N];
+        for (i, arg) in args.iter_mut().enumerate() {
+            if i > 0 {
+                self.expect(Token::Comma, "expected ,")?;
+            }
+            *arg = self.parse_designator()?;
+        }
+        self.expect(Token::RParen, "expected )")?;
+        Ok(args)
+    }
+
+    fn parse_op(&mut self) -> Result<Op, InvalidPattern> {
+        match self.next()? {
+            (_, Some(Token::Op(op))) => Ok(op),
+            (offset, _) => Err(self.error(offset, "expected operator")),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<usize, InvalidPattern> {
+        match self.next()? {
+            (offset, Some(Token::Word(word))) => {
+                btoi::btou(&word).map_err(|_| self.error(offset, "expected number"))
+            }
+            (offset, _) => Err(self.error(offset, "expected number")),
+        }
+    }
+}
+
+/// A pattern over positions, in the spirit of
+/// [Chess Query Language](http://www.gadycosteff.com/cql/).
+///
+/// Patterns are tested against every position in the mainline of a game,
+/// including the starting position.
+///
+/// # Syntax
+///
+/// Piece designators select pieces by type and square:
+///
+/// * `K`, `Q`, `R`, `B`, `N`, `P` for white pieces, lowercase letters for
+///   black pieces, `A` for any white piece and `a` for any black piece.
+/// * Several piece types in brackets, like `[RQ]`.
+/// * Optionally followed by a file and rank, each given as a single
+///   letter or digit, or as a set in brackets, like `Ne5`, `P[a-h]7` or
+///   `[RQ][ah][18]`.
+///
+/// A designator on its own matches if any such piece exists. Other
+/// patterns are:
+///
+/// | Pattern | Matches if |
+/// | --- | --- |
+/// | `attacks(X, Y)` | a piece in `X` attacks a piece in `Y` |
+/// | `fork(X, Y, Z)` | a single piece in `X` attacks distinct pieces in `Y` and in `Z` |
+/// | `behind(X, Y)` | a piece in `X` stands directly behind a piece in `Y` on the same file, as seen from the owner of `Y` |
+/// | `count(X) >= n` | the number of pieces in `X` compares to `n` |
+/// | `material = KRPkr` | the material is exactly as given |
+/// | `check`, `mate`, `stalemate` | the side to move is in check, checkmated or stalemated |
+/// | `wtm`, `btm` | White or Black is to move |
+/// | `ply >= n` | the number of plies played compares to `n` |
+/// | `repeated n` | the position occurred at least `n` times in the game |
+///
+/// Comparisons are `=`, `!=`, `<`, `<=`, `>` and `>=`. Patterns can be
+/// combined with `and`, `or`, `not` and parentheses.
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::{BufferedReader, Pattern};
+///
+/// // A white knight forks king and queen.
+/// let pattern: Pattern = "fork(N, k, q)".parse()?;
+///
+/// let pgn = b"[FEN \"8/q3k3/8/8/1N6/8/8/4K3 w - - 0 1\"]\n\n1. Nc6+ Kd6 2. Nxa7 *";
+///
+/// let mut plies = Vec::new();
+/// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+/// reader.search_pattern(&pattern, |hit| plies.push(hit.ply))?;
+/// assert_eq!(plies, [1]);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Pattern {
+    expr: Expr,
+}
+
+impl Pattern {
+    /// Compiles a pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidPattern`] error for syntax errors, and for
+    /// parentheses or `not` nested more than 100 levels deep.
+    ///
+    /// [`InvalidPattern`]: struct.InvalidPattern.html
+    pub fn parse(pattern: &str) -> Result<Pattern, InvalidPattern> {
+        let mut parser = Parser {
+            input: pattern.as_bytes(),
+            pos: 0,
+            peeked: None,
+            depth: 0,
+        };
+        let expr = parser.parse_or()?;
+        match parser.next()? {
+            (_, None) => Ok(Pattern { expr }),
+            (offset, Some(_)) => Err(parser.error(offset, "expected and, or or end")),
+        }
+    }
+
+    /// Tests a single position, as if it were reached after `ply` plies
+    /// without repetitions.
+    pub fn matches(&self, pos: &Chess, ply: usize) -> bool {
+        self.expr.eval(&Context {
+            pos,
+            ply,
+            repetitions: 1,
+        })
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = InvalidPattern;
+
+    fn from_str(s: &str) -> Result<Pattern, InvalidPattern> {
+        Pattern::parse(s)
+    }
+}
+
+/// Error when compiling an invalid [`Pattern`](struct.Pattern.html).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct InvalidPattern {
+    offset: usize,
+    reason: &'static str,
+}
+
+impl InvalidPattern {
+    /// Returns the byte offset of the error in the pattern.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for InvalidPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid pattern at offset {}: {}",
+            self.offset, self.reason
+        )
+    }
+}
+
+impl Error for InvalidPattern {}
+
+struct Matcher<'a> {
+    pattern: &'a Pattern,
+    replay: Replay,
+    history: Vec<u64>,
+    plies: Vec<usize>,
+}
+
+impl<'a> Matcher<'a> {
+    fn check(&mut self) {
+        let key = hash(&self.replay.pos);
+        self.history.push(key);
+        let ctx = Context {
+            pos: &self.replay.pos,
+            ply: self.replay.ply,
+            repetitions: self.history.iter().filter(|&&k| k == key).count(),
+        };
+        if self.pattern.expr.eval(&ctx) {
+            self.plies.push(self.replay.ply);
+        }
+    }
+}
+
+impl<'a> Visitor for Matcher<'a> {
+    type Result = Vec<usize>;
+
+    fn begin_game(&mut self) {
+        self.replay = Replay::new();
+        self.history.clear();
+        self.plies.clear();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        self.replay.header(key, value);
+    }
+
+    fn end_headers(&mut self) -> Skip {
+        if !self.replay.done {
+            self.check();
+        }
+        Skip(self.replay.done)
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        if self.replay.play(&san_plus).is_some() {
+            self.check();
+        }
+    }
+
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+        Skip(true) // stay in the mainline
+    }
+
+    fn end_game(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.plies)
+    }
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Replays the mainlines of all remaining games and calls `f` for each
+    /// position that matches the pattern. Returns the number of games
+    /// searched.
+    ///
+    /// Unlike
+    /// [`search_positions()`](struct.BufferedReader.html#method.search_positions),
+    /// every matching ply of a game is reported. Replaying a game stops at
+    /// the first illegal move.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub fn search_pattern<F>(&mut self, pattern: &Pattern, mut f: F) -> io::Result<u64>
+    where
+        F: FnMut(PositionHit),
+    {
+        let mut matcher = Matcher {
+            pattern,
+            replay: Replay::new(),
+            history: Vec::new(),
+            plies: Vec::new(),
+        };
+
+        let mut games = 0;
+        while self.has_more()? {
+            let game = self.games_read();
+            let offset = self.bytes_consumed();
+            if let Some(plies) = self.read_game(&mut matcher)? {
+                for ply in plies {
+                    f(PositionHit { game, ply, offset });
+                }
+            }
+            games += 1;
+        }
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::{fen::Fen, CastlingMode};
+
+    use super::*;
+
+    fn matches(pattern: &str, fen: &str) -> bool {
+        let pos: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+        Pattern::parse(pattern).unwrap().matches(&pos, 0)
+    }
+
+    #[test]
+    fn test_patterns() {
+        let fen = "4k3/1P6/8/8/8/8/8/1R2K3 w - - 0 1";
+        assert!(matches("behind(R, P[a-h]7)", fen));
+        assert!(matches(
+            "Pb7 and not p and count([RQ]) = 1 and material = KRPk",
+            fen
+        ));
+        assert!(matches("wtm and count(A) = 3 and attacks(R, Pb7)", fen));
+        assert!(!matches(
+            "behind(R, P[ac-h]7) or behind(K, P) or check",
+            fen
+        ));
+
+        let fen = "r3k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        assert!(matches(
+            "attacks(Ra1, ra8) and attacks(r, R) and not fork(R, r, k)",
+            fen
+        ));
+
+        let fen = "8/q3k3/2N5/8/8/8/8/4K3 b - - 0 1";
+        assert!(matches("fork(N, k, q) and check and btm", fen));
+        assert!(!matches("fork(N, q, q) or fork(N, k, k)", fen));
+    }
+
+    #[test]
+    fn test_search_pattern() -> io::Result<()> {
+        let pgn = b"1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 (4... e5) *\n";
+
+        let pattern = Pattern::parse("repeated 3 or (ply >= 8 and btm)").unwrap();
+        let mut hits = Vec::new();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        assert_eq!(
+            reader.search_pattern(&pattern, |hit| hits.push(hit.ply))?,
+            1
+        );
+        assert_eq!(hits, [8]);
+
+        assert_eq!(Pattern::parse("fork(N, k q)").unwrap_err().offset(), 10);
+        assert_eq!(Pattern::parse("N[i]3").unwrap_err().offset(), 0);
+        assert_eq!(Pattern::parse("count(P) 3").unwrap_err().offset(), 9);
+        let deep = "not (".repeat(100_000);
+        assert_eq!(
+            Pattern::parse(&deep).unwrap_err().offset(),
+            5 * MAX_DEPTH / 2
+        );
+        Ok(())
+    }
+}
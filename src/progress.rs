@@ -0,0 +1,126 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+/// Progress of a reader, as passed to the callback registered with
+/// [`BufferedReader::set_progress()`](struct.BufferedReader.html#method.set_progress).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Progress {
+    /// Number of bytes consumed from the source, excluding bytes that have
+    /// been buffered but not yet parsed.
+    pub bytes: u64,
+    /// Number of games read or skipped.
+    pub games: u64,
+    /// Total size of the source in bytes, if known.
+    pub total_bytes: Option<u64>,
+    /// Whether the end of the source has been reached.
+    pub done: bool,
+}
+
+impl Progress {
+    /// Returns the fraction of the source that has been consumed, if the
+    /// total size is known.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total_bytes.map(|total| {
+            if total == 0 {
+                1.0
+            } else {
+                self.bytes as f64 / total as f64
+            }
+        })
+    }
+}
+
+/// How often to report progress.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ProgressInterval {
+    /// After each game that crosses a multiple of `n` consumed bytes.
+    Bytes(u64),
+    /// After every `n` games.
+    Games(u64),
+}
+
+impl ProgressInterval {
+    fn counter(self, progress: &Progress) -> u64 {
+        match self {
+            ProgressInterval::Bytes(_) => progress.bytes,
+            ProgressInterval::Games(_) => progress.games,
+        }
+    }
+
+    fn step(self) -> u64 {
+        match self {
+            ProgressInterval::Bytes(n) | ProgressInterval::Games(n) => n.max(1),
+        }
+    }
+}
+
+pub(crate) struct ProgressReporter {
+    interval: ProgressInterval,
+    total_bytes: Option<u64>,
+    next: u64,
+    done: bool,
+    callback: Box<dyn FnMut(&Progress) + Send>,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(
+        interval: ProgressInterval,
+        total_bytes: Option<u64>,
+        callback: Box<dyn FnMut(&Progress) + Send>,
+    ) -> ProgressReporter {
+        ProgressReporter {
+            interval,
+            total_bytes,
+            next: interval.step(),
+            done: false,
+            callback,
+        }
+    }
+
+    /// Called after each game, or with `done` at the end of the source.
+    pub(crate) fn update(&mut self, bytes: u64, games: u64, done: bool) {
+        if self.done {
+            return;
+        }
+
+        let progress = Progress {
+            bytes,
+            games,
+            total_bytes: self.total_bytes,
+            done,
+        };
+
+        let counter = self.interval.counter(&progress);
+        if done || counter >= self.next {
+            let step = self.interval.step();
+            self.next = (counter / step + 1) * step;
+            self.done = done;
+            (self.callback)(&progress);
+        }
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("interval", &self.interval)
+            .field("total_bytes", &self.total_bytes)
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
@@ -16,8 +16,10 @@
 
 use std::{
     cmp::min,
-    io::{self, Chain, Cursor, Read},
-    ptr,
+    error::Error,
+    fmt,
+    io::{self, Chain, Cursor, Read, Seek, SeekFrom, Write},
+    mem, ptr,
 };
 
 use shakmaty::{
@@ -27,13 +29,97 @@ use shakmaty::{
 use slice_deque::SliceDeque;
 
 use crate::{
+    limits::{Limit, LimitAction, LimitExceeded, Limited, Limits},
+    progress::{Progress, ProgressInterval, ProgressReporter},
     types::{Nag, RawComment, RawHeader, Skip},
     visitor::{SkipVisitor, Visitor},
 };
 
 const MIN_BUFFER_SIZE: usize = 8192;
 
-trait ReadPgn {
+/// A header found by `ReadPgn::read_header()`, relative to the start of the
+/// buffer.
+#[derive(Debug, Clone)]
+pub(crate) struct HeaderSpan {
+    /// End of the key.
+    pub(crate) key_end: usize,
+    /// Start of the value, after the opening quote.
+    pub(crate) value_start: usize,
+    /// End of the value, before the closing quote.
+    pub(crate) value_end: usize,
+    /// Length of the header, up to and including the closing quote.
+    pub(crate) len: usize,
+}
+
+impl HeaderSpan {
+    pub(crate) fn key<'a>(&self, buffer: &'a [u8]) -> &'a [u8] {
+        &buffer[..self.key_end]
+    }
+
+    pub(crate) fn value<'a>(&self, buffer: &'a [u8]) -> &'a [u8] {
+        &buffer[self.value_start..self.value_end]
+    }
+}
+
+/// A token of the movetext, as read by `ReadPgn::read_token()`.
+#[derive(Debug, Clone)]
+pub(crate) enum MovetextToken {
+    San(SanPlus),
+    Nag(Nag),
+    /// The buffer starts with a comment of the given length, followed by
+    /// the closing `}`.
+    Comment(usize),
+    BeginVariation,
+    EndVariation,
+    Outcome(Option<Outcome>),
+}
+
+/// Tracks the ply of the next move and the open variations of the
+/// movetext.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Line {
+    ply: usize,
+    plies: Vec<usize>,
+}
+
+impl Line {
+    /// Returns the ply of the next move and advances.
+    pub(crate) fn san(&mut self) -> usize {
+        let ply = self.ply;
+        self.ply += 1;
+        ply
+    }
+
+    /// Opens a variation replacing the previous move. Returns its depth and
+    /// branch ply.
+    pub(crate) fn begin_variation(&mut self) -> (usize, usize) {
+        self.plies.push(self.ply);
+        self.ply = self.ply.saturating_sub(1);
+        (self.plies.len(), self.ply)
+    }
+
+    /// Closes the innermost variation. Returns `false` if there is no open
+    /// variation.
+    pub(crate) fn end_variation(&mut self) -> bool {
+        match self.plies.pop() {
+            Some(ply) => {
+                self.ply = ply;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Whether `ch` ends a move, move number or other plain movetext token.
+pub(crate) fn is_token_end(ch: u8) -> bool {
+    matches!(
+        ch,
+        b' ' | b'\t' | b'\n' | b'\r' | b'{' | b'}' | b'(' | b')' | b'!' | b'?' | b'$' | b';' | b'.'
+    )
+}
+
+pub(crate) trait ReadPgn {
     type Err;
 
     /// Fill the buffer. The buffer must then contain at least MIN_BUFFER_SIZE
@@ -49,6 +135,18 @@ trait ReadPgn {
     /// Constructs a parser error.
     fn invalid_data() -> Self::Err;
 
+    /// Whether to resynchronize at the next plausible game start after
+    /// parser errors.
+    fn resync(&self) -> bool {
+        false
+    }
+
+    /// Whether the end of the source was faked to cut off a game. No
+    /// further calls are made to the visitor, except for `end_game()`.
+    fn truncated(&self) -> bool {
+        false
+    }
+
     fn peek(&self) -> Option<u8> {
         self.buffer().get(0).cloned()
     }
@@ -91,6 +189,52 @@ trait ReadPgn {
         Ok(())
     }
 
+    /// Skips to the next line starting with `[Event ` that follows a blank
+    /// line, or to the end of the source.
+    fn skip_to_game_start(&mut self) -> Result<(), Self::Err> {
+        const MARKER: &[u8] = b"\r\n[Event ";
+
+        while self.fill_buffer_and_peek()?.is_some() {
+            let found = memchr::memchr_iter(b'\n', self.buffer()).find_map(|eol| {
+                let next = &self.buffer()[eol + 1..];
+                if next.starts_with(&MARKER[1..]) {
+                    Some(eol + 2)
+                } else if next.starts_with(MARKER) {
+                    Some(eol + 3)
+                } else {
+                    None
+                }
+            });
+
+            match found {
+                Some(start) => {
+                    self.consume(start);
+                    return Ok(());
+                }
+                None if self.remaining() > MARKER.len() + 1 => {
+                    // Keep enough to find a marker that crosses the end of
+                    // the buffer, including the line break before it.
+                    let remaining = self.remaining();
+                    self.consume(remaining - (MARKER.len() + 1));
+                }
+                None => self.consume_all(),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Called on parser errors. Returns whether the error has been handled
+    /// by resynchronizing.
+    fn recover(&mut self) -> Result<bool, Self::Err> {
+        if self.resync() {
+            self.skip_to_game_start()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     fn skip_line(&mut self) -> Result<(), Self::Err> {
         self.skip_until(b'\n')?;
         self.bump();
@@ -138,7 +282,10 @@ trait ReadPgn {
         Ok(())
     }
 
-    fn read_headers<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Self::Err> {
+    /// Finds the next header, skipping malformed header lines and escaped
+    /// lines. The buffer then starts with the key of the header. Returns
+    /// `None` at the end of the headers.
+    fn read_header(&mut self) -> Result<Option<HeaderSpan>, Self::Err> {
         while let Some(ch) = self.fill_buffer_and_peek()? {
             match ch {
                 b'[' => {
@@ -152,8 +299,10 @@ trait ReadPgn {
                             continue;
                         }
                         None => {
-                            self.consume_all();
-                            self.skip_line()?;
+                            if !self.recover()? {
+                                self.consume_all();
+                                self.skip_line()?;
+                            }
                             return Err(Self::invalid_data());
                         }
                     };
@@ -181,25 +330,45 @@ trait ReadPgn {
                                 right_quote = min(right_quote + delta + 2, self.remaining());
                             }
                             None => {
-                                self.consume_all();
-                                self.skip_line()?;
+                                if !self.recover()? {
+                                    self.consume_all();
+                                    self.skip_line()?;
+                                }
                                 return Err(Self::invalid_data());
                             }
                         }
                     };
 
-                    visitor.header(
-                        &self.buffer()[..space],
-                        RawHeader(&self.buffer()[value_start..right_quote]),
-                    );
-                    self.consume(consumed);
-                    self.skip_ket()?;
+                    return Ok(Some(HeaderSpan {
+                        key_end: space,
+                        value_start,
+                        value_end: right_quote,
+                        len: consumed,
+                    }));
                 }
                 b'%' => self.skip_line()?,
-                _ => return Ok(()),
+                _ => return Ok(None),
             }
         }
 
+        Ok(None)
+    }
+
+    /// Consumes a header found by `read_header()`.
+    fn end_header(&mut self, header: &HeaderSpan) -> Result<(), Self::Err> {
+        self.consume(header.len);
+        self.skip_ket()
+    }
+
+    fn read_headers<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Self::Err> {
+        while let Some(header) = self.read_header()? {
+            visitor.header(
+                header.key(self.buffer()),
+                RawHeader(header.value(self.buffer())),
+            );
+            self.end_header(&header)?;
+        }
+
         Ok(())
     }
 
@@ -242,16 +411,18 @@ trait ReadPgn {
     fn find_token_end(&mut self, start: usize) -> usize {
         let mut end = start;
         for &ch in &self.buffer()[start..] {
-            match ch {
-                b' ' | b'\t' | b'\n' | b'\r' | b'{' | b'}' | b'(' | b')' | b'!' | b'?' | b'$'
-                | b';' | b'.' => break,
-                _ => end += 1,
+            if is_token_end(ch) {
+                break;
             }
+            end += 1;
         }
         end
     }
 
-    fn read_movetext<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Self::Err> {
+    /// Reads the next token of the movetext, skipping move numbers,
+    /// whitespace and escaped lines. Returns `None` at the end of the
+    /// movetext.
+    fn read_token(&mut self) -> Result<Option<MovetextToken>, Self::Err> {
         while let Some(ch) = self.fill_buffer_and_peek()? {
             match ch {
                 b'{' => {
@@ -261,14 +432,15 @@ trait ReadPgn {
                     {
                         right_brace
                     } else {
-                        self.consume_all();
-                        self.skip_until(b'}')?;
-                        self.bump();
+                        if !self.recover()? {
+                            self.consume_all();
+                            self.skip_until(b'}')?;
+                            self.bump();
+                        }
                         return Err(Self::invalid_data());
                     };
 
-                    visitor.comment(RawComment(&self.buffer()[..right_brace]));
-                    self.consume(right_brace + 1);
+                    return Ok(Some(MovetextToken::Comment(right_brace)));
                 }
                 b'\n' => {
                     self.bump();
@@ -279,12 +451,12 @@ trait ReadPgn {
                             self.skip_line()?;
                         }
                         Some(b'[') | Some(b'\n') => {
-                            break;
+                            return Ok(None);
                         }
                         Some(b'\r') => {
                             self.bump();
                             if self.peek() == Some(b'\n') {
-                                break;
+                                return Ok(None);
                             }
                         }
                         _ => continue,
@@ -298,12 +470,12 @@ trait ReadPgn {
                     self.bump();
                     if self.buffer().starts_with(b"-0") {
                         self.consume(2);
-                        visitor.outcome(Some(Outcome::Decisive {
+                        return Ok(Some(MovetextToken::Outcome(Some(Outcome::Decisive {
                             winner: Color::White,
-                        }));
+                        }))));
                     } else if self.buffer().starts_with(b"/2-1/2") {
                         self.consume(6);
-                        visitor.outcome(Some(Outcome::Draw));
+                        return Ok(Some(MovetextToken::Outcome(Some(Outcome::Draw))));
                     } else {
                         let token_end = self.find_token_end(0);
                         self.consume(token_end);
@@ -313,9 +485,9 @@ trait ReadPgn {
                     self.bump();
                     if self.buffer().starts_with(b"-1") {
                         self.consume(2);
-                        visitor.outcome(Some(Outcome::Decisive {
+                        return Ok(Some(MovetextToken::Outcome(Some(Outcome::Decisive {
                             winner: Color::Black,
-                        }));
+                        }))));
                     } else if self.buffer().starts_with(b"-0") {
                         // Castling notation with zeros.
                         self.consume(2);
@@ -330,10 +502,10 @@ trait ReadPgn {
                             Some(b'#') => Some(Suffix::Checkmate),
                             _ => None,
                         };
-                        visitor.san(SanPlus {
+                        return Ok(Some(MovetextToken::San(SanPlus {
                             san: San::Castle(side),
                             suffix,
-                        });
+                        })));
                     } else {
                         let token_end = self.find_token_end(0);
                         self.consume(token_end);
@@ -341,36 +513,33 @@ trait ReadPgn {
                 }
                 b'(' => {
                     self.bump();
-                    if let Skip(true) = visitor.begin_variation() {
-                        self.skip_variation()?;
-                    }
+                    return Ok(Some(MovetextToken::BeginVariation));
                 }
                 b')' => {
                     self.bump();
-                    visitor.end_variation();
+                    return Ok(Some(MovetextToken::EndVariation));
                 }
                 b'$' => {
                     self.bump();
                     let token_end = self.find_token_end(0);
-                    if let Ok(nag) = btoi::btou(&self.buffer()[..token_end]) {
-                        visitor.nag(Nag(nag));
-                    }
+                    let nag = btoi::btou(&self.buffer()[..token_end]);
                     self.consume(token_end);
+                    if let Ok(nag) = nag {
+                        return Ok(Some(MovetextToken::Nag(Nag(nag))));
+                    }
                 }
                 b'!' => {
                     self.bump();
                     match self.peek() {
                         Some(b'!') => {
                             self.bump();
-                            visitor.nag(Nag::BRILLIANT_MOVE);
+                            return Ok(Some(MovetextToken::Nag(Nag::BRILLIANT_MOVE)));
                         }
                         Some(b'?') => {
                             self.bump();
-                            visitor.nag(Nag::SPECULATIVE_MOVE);
-                        }
-                        _ => {
-                            visitor.nag(Nag::GOOD_MOVE);
+                            return Ok(Some(MovetextToken::Nag(Nag::SPECULATIVE_MOVE)));
                         }
+                        _ => return Ok(Some(MovetextToken::Nag(Nag::GOOD_MOVE))),
                     }
                 }
                 b'?' => {
@@ -378,20 +547,18 @@ trait ReadPgn {
                     match self.peek() {
                         Some(b'!') => {
                             self.bump();
-                            visitor.nag(Nag::DUBIOUS_MOVE);
+                            return Ok(Some(MovetextToken::Nag(Nag::DUBIOUS_MOVE)));
                         }
                         Some(b'?') => {
                             self.bump();
-                            visitor.nag(Nag::BLUNDER);
-                        }
-                        _ => {
-                            visitor.nag(Nag::MISTAKE);
+                            return Ok(Some(MovetextToken::Nag(Nag::BLUNDER)));
                         }
+                        _ => return Ok(Some(MovetextToken::Nag(Nag::MISTAKE))),
                     }
                 }
                 b'*' => {
-                    visitor.outcome(None);
                     self.bump();
+                    return Ok(Some(MovetextToken::Outcome(None)));
                 }
                 b' ' | b'\t' | b'\r' | b'P' | b'.' => {
                     self.bump();
@@ -399,15 +566,53 @@ trait ReadPgn {
                 _ => {
                     let token_end = self.find_token_end(1);
                     if ch > b'9' || ch == b'-' {
-                        if let Ok(san) = SanPlus::from_ascii(&self.buffer()[..token_end]) {
-                            visitor.san(san);
+                        let san = SanPlus::from_ascii(&self.buffer()[..token_end]);
+                        self.consume(token_end);
+                        if let Ok(san) = san {
+                            return Ok(Some(MovetextToken::San(san)));
                         }
+                    } else {
+                        self.consume(token_end);
                     }
-                    self.consume(token_end);
                 }
             }
         }
 
+        Ok(None)
+    }
+
+    fn read_movetext<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Self::Err> {
+        let mut line = Line::default();
+
+        while let Some(token) = self.read_token()? {
+            match token {
+                MovetextToken::San(san_plus) => visitor.san_at(line.san(), san_plus),
+                MovetextToken::Nag(nag) => visitor.nag(nag),
+                MovetextToken::Comment(len) => {
+                    visitor.comment(RawComment(&self.buffer()[..len]));
+                    self.consume(len + 1);
+                }
+                MovetextToken::BeginVariation => {
+                    let (depth, ply) = line.begin_variation();
+                    if let Skip(true) = visitor.begin_variation(depth, ply) {
+                        self.skip_variation()?;
+                    }
+                }
+                MovetextToken::EndVariation => {
+                    if line.end_variation() {
+                        visitor.end_variation();
+                    }
+                }
+                MovetextToken::Outcome(outcome) => visitor.outcome(outcome),
+            }
+        }
+
+        if !self.truncated() {
+            while line.end_variation() {
+                visitor.end_variation();
+            }
+        }
+
         Ok(())
     }
 
@@ -478,6 +683,9 @@ trait ReadPgn {
         visitor.begin_game();
         visitor.begin_headers();
         self.read_headers(visitor)?;
+        if self.truncated() {
+            return Ok(Some(visitor.end_game()));
+        }
         if let Skip(false) = visitor.end_headers() {
             self.read_movetext(visitor)?;
         } else {
@@ -518,6 +726,69 @@ impl AsRef<[u8]> for Buffer {
 pub struct BufferedReader<R> {
     inner: R,
     buffer: Buffer,
+    bytes_read: u64,
+    games: u64,
+    boundary: Checkpoint,
+    recovery: Recovery,
+    limits: Option<Limits>,
+    budget_end: Option<u64>,
+    over_budget: bool,
+    progress: Option<ProgressReporter>,
+}
+
+/// What to do after irrecoverable parser errors.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Recovery {
+    /// Return an error. Skip ahead to some point after the error, possibly
+    /// far beyond the damaged game. This is the default.
+    #[default]
+    Fail,
+    /// Return an error with a [`DamagedGame`] payload, after
+    /// resynchronizing at the next plausible game start: a line starting
+    /// with `[Event ` that follows a blank line. Reading can continue with
+    /// the next game.
+    ///
+    /// [`DamagedGame`]: struct.DamagedGame.html
+    Resync,
+}
+
+/// Payload of the error returned for a game that could not be parsed, if
+/// [`Recovery::Resync`](enum.Recovery.html#variant.Resync) is enabled.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DamagedGame {
+    /// Byte offset of the start of the damaged game.
+    pub start: u64,
+    /// Byte offset where reading resumes.
+    pub end: u64,
+}
+
+impl DamagedGame {
+    /// Extracts the damaged byte range from an error returned by
+    /// [`BufferedReader::read_game()`](struct.BufferedReader.html#method.read_game),
+    /// if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&DamagedGame> {
+        err.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+}
+
+impl fmt::Display for DamagedGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "damaged game at bytes {}..{}", self.start, self.end)
+    }
+}
+
+impl Error for DamagedGame {}
+
+/// A position between two games, from which reading can be resumed with
+/// [`BufferedReader::resume()`](struct.BufferedReader.html#method.resume).
+///
+/// The fields can be persisted as they are.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Checkpoint {
+    /// Byte offset of the next game in the source.
+    pub offset: u64,
+    /// Number of games read or skipped before the next game.
+    pub games: u64,
 }
 
 impl<T: AsRef<[u8]>> BufferedReader<Cursor<T>> {
@@ -554,6 +825,14 @@ impl<R: Read> BufferedReader<R> {
         let mut reader = BufferedReader {
             inner,
             buffer: Buffer::new(),
+            bytes_read: 0,
+            games: 0,
+            boundary: Checkpoint::default(),
+            recovery: Recovery::Fail,
+            limits: None,
+            budget_end: None,
+            over_budget: false,
+            progress: None,
         };
 
         unsafe {
@@ -578,7 +857,69 @@ impl<R: Read> BufferedReader<R> {
     /// * I/O error from the underlying reader.
     /// * Irrecoverable parser errors.
     pub fn read_game<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<Option<V::Result>> {
-        ReadPgn::read_game(self, visitor)
+        loop {
+            let start = self.game_start()?;
+            let result = match self.limits.take() {
+                Some(limits) => {
+                    let result = self.read_game_limited(start, &limits, visitor);
+                    self.limits = Some(limits);
+                    match result {
+                        Ok(Err(limit)) => {
+                            self.game_done(true);
+                            match self.limits.as_ref().map(|limits| limits.action) {
+                                Some(LimitAction::Skip) => continue,
+                                _ => {
+                                    return Err(io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        LimitExceeded { limit, start },
+                                    ))
+                                }
+                            }
+                        }
+                        Ok(Ok(result)) => Ok(result),
+                        Err(err) => Err(err),
+                    }
+                }
+                None => ReadPgn::read_game(self, visitor),
+            };
+
+            return match result {
+                Ok(result) => {
+                    self.game_done(result.is_some());
+                    Ok(result)
+                }
+                Err(err) => Err(self.damaged(start, err)),
+            };
+        }
+    }
+
+    fn read_game_limited<V: Visitor>(
+        &mut self,
+        start: u64,
+        limits: &Limits,
+        visitor: &mut V,
+    ) -> io::Result<Result<Option<V::Result>, Limit>> {
+        self.budget_end = limits.max_game_bytes.map(|max| start + max);
+        let mut limited = Limited::new(visitor, limits);
+        let result = ReadPgn::read_game(self, &mut limited);
+        self.budget_end = None;
+
+        if mem::take(&mut self.over_budget) {
+            // Skip the rest of the game.
+            if !limited.in_movetext() {
+                self.skip_line()?;
+                self.read_headers(&mut SkipVisitor)?;
+            }
+            self.skip_movetext()?;
+            self.skip_whitespace()?;
+            return Ok(Err(Limit::GameBytes));
+        }
+
+        let result = result?;
+        Ok(match limited.exceeded() {
+            Some(limit) => Err(limit),
+            None => Ok(result),
+        })
     }
 
     /// Skip a single game, if any.
@@ -588,7 +929,199 @@ impl<R: Read> BufferedReader<R> {
     /// * I/O error from the underlying reader.
     /// * Irrecoverable parser errors.
     pub fn skip_game<V: Visitor>(&mut self) -> io::Result<bool> {
-        ReadPgn::skip_game(self)
+        let start = self.game_start()?;
+        match ReadPgn::skip_game(self) {
+            Ok(skipped) => {
+                self.game_done(skipped);
+                Ok(skipped)
+            }
+            Err(err) => Err(self.damaged(start, err)),
+        }
+    }
+
+    fn game_start(&mut self) -> io::Result<u64> {
+        if self.recovery == Recovery::Resync || self.limits.is_some() {
+            self.skip_bom()?;
+            self.skip_whitespace()?;
+        }
+        Ok(self.bytes_consumed())
+    }
+
+    pub(crate) fn damaged(&mut self, start: u64, err: io::Error) -> io::Error {
+        if self.recovery == Recovery::Resync
+            && err.kind() == io::ErrorKind::InvalidData
+            && err.get_ref().is_none()
+        {
+            self.game_done(true);
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                DamagedGame {
+                    start,
+                    end: self.bytes_consumed(),
+                },
+            )
+        } else {
+            err
+        }
+    }
+
+    /// Enforces resource limits on each game, so that untrusted input
+    /// cannot make the visitor do unbounded work. Games that exceed a limit
+    /// are rejected or skipped, as configured by
+    /// [`Limits::action`](struct.Limits.html#structfield.action).
+    ///
+    /// Limits do not apply to
+    /// [`skip_game()`](struct.BufferedReader.html#method.skip_game).
+    ///
+    /// ```
+    /// use pgn_reader::{BufferedReader, Limit, LimitExceeded, Limits};
+    /// # struct MyVisitor;
+    /// # impl pgn_reader::Visitor for MyVisitor {
+    /// #     type Result = ();
+    /// #     fn end_game(&mut self) {}
+    /// # }
+    ///
+    /// let pgn = b"1. e4 (1. d4 (1. c4 (1. Nf3))) *";
+    ///
+    /// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+    /// reader.set_limits(Limits {
+    ///     max_depth: Some(2),
+    ///     ..Limits::default()
+    /// });
+    ///
+    /// let err = reader.read_game(&mut MyVisitor).unwrap_err();
+    /// assert_eq!(LimitExceeded::from_io_error(&err).map(|e| e.limit), Some(Limit::Depth));
+    /// ```
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = Some(limits);
+    }
+
+    /// Sets what to do after irrecoverable parser errors.
+    ///
+    /// ```
+    /// use pgn_reader::{BufferedReader, DamagedGame, Recovery};
+    /// # struct MyVisitor;
+    /// # impl pgn_reader::Visitor for MyVisitor {
+    /// #     type Result = ();
+    /// #     fn end_game(&mut self) {}
+    /// # }
+    ///
+    /// let pgn = b"[Event \"A\"]\n\n1. e4 { unterminated *\n\n[Event \"B\"]\n\n1. d4 *";
+    ///
+    /// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+    /// reader.set_recovery(Recovery::Resync);
+    ///
+    /// let mut visitor = MyVisitor;
+    /// let games: Vec<_> = reader.into_iter(&mut visitor).collect();
+    /// assert_eq!(games.len(), 2);
+    /// let err = games[0].as_ref().unwrap_err();
+    /// assert_eq!(
+    ///     DamagedGame::from_io_error(err),
+    ///     Some(&DamagedGame { start: 0, end: 37 })
+    /// );
+    /// assert!(games[1].is_ok());
+    /// ```
+    pub fn set_recovery(&mut self, recovery: Recovery) {
+        self.recovery = recovery;
+    }
+
+    pub(crate) fn game_done(&mut self, found: bool) {
+        if found {
+            self.games += 1;
+        }
+        self.boundary = Checkpoint {
+            offset: self.bytes_consumed(),
+            games: self.games,
+        };
+        if let Some(ref mut progress) = self.progress {
+            let bytes = self.bytes_read - self.buffer.inner.len() as u64;
+            progress.update(bytes, self.games, !found);
+        }
+    }
+
+    /// Returns the number of bytes consumed from the underlying reader so
+    /// far. Bytes that have been read into the internal buffer, but not yet
+    /// parsed, are not counted.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # fn try_main() -> io::Result<()> {
+    /// use pgn_reader::BufferedReader;
+    /// # struct MyVisitor;
+    /// # impl pgn_reader::Visitor for MyVisitor {
+    /// #     type Result = ();
+    /// #     fn end_game(&mut self) {}
+    /// # }
+    ///
+    /// let mut reader = BufferedReader::new_cursor(&b"1. e4 *\n\n1. d4 *"[..]);
+    /// reader.read_game(&mut MyVisitor)?;
+    /// assert_eq!(reader.bytes_consumed(), 9);
+    /// # Ok(())
+    /// # }
+    /// # try_main().unwrap();
+    /// ```
+    pub fn bytes_consumed(&self) -> u64 {
+        self.bytes_read - self.buffer.inner.len() as u64
+    }
+
+    /// Returns the number of games read or skipped so far.
+    pub fn games_read(&self) -> u64 {
+        self.games
+    }
+
+    /// Returns a checkpoint after the last game that has been completely
+    /// read or skipped. Resuming from the checkpoint yields exactly the
+    /// games that have not been delivered yet, including a game that
+    /// failed with an error (unless it was reported as a [`DamagedGame`]).
+    ///
+    /// [`DamagedGame`]: struct.DamagedGame.html
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.boundary
+    }
+
+    /// Registers a callback that is invoked with the current [`Progress`]
+    /// after games, as often as specified by `interval`, and once more
+    /// when the end of the source is reached. `total_bytes` is passed
+    /// through to the callback and should be the length of the source, if
+    /// known.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # fn try_main() -> io::Result<()> {
+    /// use std::fs::File;
+    /// use pgn_reader::{BufferedReader, ProgressInterval};
+    /// # struct MyVisitor;
+    /// # impl pgn_reader::Visitor for MyVisitor {
+    /// #     type Result = ();
+    /// #     fn end_game(&mut self) {}
+    /// # }
+    ///
+    /// let file = File::open("example.pgn")?;
+    /// let total_bytes = file.metadata()?.len();
+    ///
+    /// let mut reader = BufferedReader::new(file);
+    /// reader.set_progress(ProgressInterval::Bytes(1 << 20), Some(total_bytes), |progress| {
+    ///     eprintln!("{:.1}%", 100.0 * progress.fraction().unwrap_or(0.0));
+    /// });
+    /// reader.read_all(&mut MyVisitor)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Progress`]: struct.Progress.html
+    pub fn set_progress<F>(
+        &mut self,
+        interval: ProgressInterval,
+        total_bytes: Option<u64>,
+        callback: F,
+    ) where
+        F: FnMut(&Progress) + Send + 'static,
+    {
+        self.progress = Some(ProgressReporter::new(
+            interval,
+            total_bytes,
+            Box::new(callback),
+        ));
     }
 
     /// Read all games.
@@ -620,6 +1153,17 @@ impl<R: Read> BufferedReader<R> {
         Cursor::new(self.buffer).chain(self.inner)
     }
 
+    /// Gets a reference to the underlying reader.
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the bytes that have been read from the underlying reader,
+    /// but not yet consumed.
+    pub(crate) fn buffered(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+
     /// Returns whether the reader has another game to parse, but does not
     /// actually parse it.
     ///
@@ -633,10 +1177,63 @@ impl<R: Read> BufferedReader<R> {
     }
 }
 
+impl<R: Read + Seek> BufferedReader<R> {
+    /// Create a new buffered PGN reader that continues from a checkpoint
+    /// previously obtained from
+    /// [`checkpoint()`](struct.BufferedReader.html#method.checkpoint) on
+    /// the same source.
+    ///
+    /// ```
+    /// # use std::io;
+    /// # fn try_main() -> io::Result<()> {
+    /// use std::fs::File;
+    /// use pgn_reader::{BufferedReader, Checkpoint};
+    /// # struct Importer;
+    /// # impl pgn_reader::Visitor for Importer {
+    /// #     type Result = ();
+    /// #     fn end_game(&mut self) {}
+    /// # }
+    /// # fn load_checkpoint() -> Checkpoint { Checkpoint::default() }
+    /// # fn save_checkpoint(_: Checkpoint) {}
+    ///
+    /// let checkpoint = load_checkpoint();
+    /// let mut reader = BufferedReader::resume(File::open("example.pgn")?, checkpoint)?;
+    /// while reader.read_game(&mut Importer)?.is_some() {
+    ///     save_checkpoint(reader.checkpoint());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * I/O error when seeking the underlying reader.
+    pub fn resume(mut inner: R, checkpoint: Checkpoint) -> io::Result<BufferedReader<R>> {
+        inner.seek(SeekFrom::Start(checkpoint.offset))?;
+        let mut reader = BufferedReader::new(inner);
+        reader.bytes_read = checkpoint.offset;
+        reader.games = checkpoint.games;
+        reader.boundary = checkpoint;
+        Ok(reader)
+    }
+}
+
 impl<R: Read> ReadPgn for BufferedReader<R> {
     type Err = io::Error;
 
+    fn resync(&self) -> bool {
+        self.recovery == Recovery::Resync
+    }
+
     fn fill_buffer_and_peek(&mut self) -> io::Result<Option<u8>> {
+        if let Some(budget_end) = self.budget_end {
+            if self.bytes_consumed() > budget_end {
+                // Pretend the game ends here.
+                self.over_budget = true;
+                return Ok(None);
+            }
+        }
+
         while self.buffer.inner.len() < MIN_BUFFER_SIZE {
             unsafe {
                 let size = {
@@ -650,6 +1247,7 @@ impl<R: Read> ReadPgn for BufferedReader<R> {
                     break;
                 }
 
+                self.bytes_read += size as u64;
                 self.buffer.inner.move_tail(size as isize);
             }
         }
@@ -661,6 +1259,10 @@ impl<R: Read> ReadPgn for BufferedReader<R> {
         io::Error::from(io::ErrorKind::InvalidData)
     }
 
+    fn truncated(&self) -> bool {
+        self.over_budget
+    }
+
     fn buffer(&self) -> &[u8] {
         self.buffer.inner.as_slice()
     }
@@ -678,6 +1280,112 @@ impl<R: Read> ReadPgn for BufferedReader<R> {
     }
 }
 
+/// Reads games from a byte slice that holds all remaining data.
+#[derive(Debug, Clone)]
+pub(crate) struct SliceReader<'a> {
+    bytes: &'a [u8],
+    consumed: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> SliceReader<'a> {
+        SliceReader { bytes, consumed: 0 }
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub(crate) fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Returns the bytes that have not been consumed yet.
+    pub(crate) fn remaining_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub(crate) fn read_game<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> io::Result<Option<V::Result>> {
+        ReadPgn::read_game(self, visitor)
+    }
+
+    pub(crate) fn skip_game(&mut self) -> io::Result<bool> {
+        ReadPgn::skip_game(self)
+    }
+}
+
+impl<'a> ReadPgn for SliceReader<'a> {
+    type Err = io::Error;
+
+    fn fill_buffer_and_peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.bytes.first().cloned())
+    }
+
+    fn invalid_data() -> io::Error {
+        io::Error::from(io::ErrorKind::InvalidData)
+    }
+
+    fn buffer(&self) -> &[u8] {
+        self.bytes
+    }
+
+    fn consume(&mut self, bytes: usize) {
+        self.bytes = &self.bytes[bytes..];
+        self.consumed += bytes;
+    }
+}
+
+/// Copies everything consumed from a reader to a writer.
+pub(crate) struct Tee<'a, R, W> {
+    pub(crate) reader: &'a mut BufferedReader<R>,
+    pub(crate) writer: W,
+    err: Option<io::Error>,
+}
+
+impl<'a, R: Read, W: Write> Tee<'a, R, W> {
+    pub(crate) fn new(reader: &'a mut BufferedReader<R>, writer: W) -> Tee<'a, R, W> {
+        Tee {
+            reader,
+            writer,
+            err: None,
+        }
+    }
+
+    /// Returns the first error from writing, if any.
+    pub(crate) fn check(&mut self) -> io::Result<()> {
+        self.err.take().map_or(Ok(()), Err)
+    }
+}
+
+impl<'a, R: Read, W: Write> ReadPgn for Tee<'a, R, W> {
+    type Err = io::Error;
+
+    fn fill_buffer_and_peek(&mut self) -> io::Result<Option<u8>> {
+        self.reader.fill_buffer_and_peek()
+    }
+
+    fn buffer(&self) -> &[u8] {
+        self.reader.buffer()
+    }
+
+    fn consume(&mut self, n: usize) {
+        if self.err.is_none() {
+            if let Err(err) = self.writer.write_all(&self.reader.buffer()[..n]) {
+                self.err = Some(err);
+            }
+        }
+        self.reader.consume(n);
+    }
+
+    fn invalid_data() -> io::Error {
+        io::Error::from(io::ErrorKind::InvalidData)
+    }
+
+    fn resync(&self) -> bool {
+        self.reader.resync()
+    }
+}
+
 /// Iterator returned by
 /// [`BufferedReader::into_iter()`](struct.BufferedReader.html#method.into_iter).
 #[derive(Debug)]
@@ -743,6 +1451,310 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_progress() -> Result<(), io::Error> {
+        use std::sync::{Arc, Mutex};
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut reader = BufferedReader::new_cursor(&b"1. e4 *\n\n1. d4 *\n\n1. c4 *\n"[..]);
+        let sink = reports.clone();
+        reader.set_progress(ProgressInterval::Games(2), Some(26), move |progress| {
+            sink.lock().unwrap().push(progress.clone());
+        });
+        reader.read_all(&mut GameCounter::default())?;
+        assert_eq!(reader.bytes_consumed(), 26);
+        assert_eq!(reader.games_read(), 3);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            (reports[0].bytes, reports[0].games, reports[0].done),
+            (18, 2, false)
+        );
+        assert_eq!(
+            (reports[1].bytes, reports[1].games, reports[1].done),
+            (26, 3, true)
+        );
+        assert_eq!(reports[1].fraction(), Some(1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume() -> Result<(), io::Error> {
+        struct FirstSan {
+            first: Option<San>,
+        }
+
+        impl Visitor for FirstSan {
+            type Result = Option<San>;
+
+            fn san(&mut self, san_plus: SanPlus) {
+                self.first.get_or_insert(san_plus.san);
+            }
+
+            fn end_game(&mut self) -> Option<San> {
+                self.first.take()
+            }
+        }
+
+        let pgn = b"1. e4 *\n\n1. d4 {\n\n} *\n\n1. c4 *\n\n1. Nf3 *\n";
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        assert_eq!(reader.checkpoint(), Checkpoint::default());
+        reader.read_game(&mut FirstSan { first: None })?;
+        reader.read_game(&mut FirstSan { first: None })?;
+        let checkpoint = reader.checkpoint();
+        assert_eq!(checkpoint.games, 2);
+
+        let reader = BufferedReader::resume(Cursor::new(&pgn[..]), checkpoint)?;
+        let mut visitor = FirstSan { first: None };
+        let rest = reader
+            .into_iter(&mut visitor)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            rest,
+            [Some("c4".parse().unwrap()), Some("Nf3".parse().unwrap())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resync() {
+        let mut pgn = b"[Event \"A\"]\n[Site \"".to_vec();
+        pgn.extend(vec![b'x'; 3 * MIN_BUFFER_SIZE]);
+        pgn.extend(b"\r\n\r\n[Event \"B\"]\n\n1. e4 *\n\n[Event \"C\"]\n\n1. d4 *");
+
+        let mut counter = GameCounter::default();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        reader.set_recovery(Recovery::Resync);
+        let err = reader.read_game(&mut counter).unwrap_err();
+        assert_eq!(
+            DamagedGame::from_io_error(&err),
+            Some(&DamagedGame {
+                start: 0,
+                end: pgn.len() as u64 - 42,
+            })
+        );
+        assert!(reader.read_game(&mut counter).unwrap().is_some());
+        assert!(reader.read_game(&mut counter).unwrap().is_some());
+        assert!(reader.read_game(&mut counter).unwrap().is_none());
+        assert_eq!(counter.count, 2);
+        assert_eq!(reader.games_read(), 3);
+    }
+
+    #[test]
+    fn test_resync_across_buffer_boundary() {
+        // Exposes at most a small window of the input, like a reader with
+        // a tiny buffer.
+        struct Window<'a> {
+            bytes: &'a [u8],
+            pos: usize,
+            size: usize,
+        }
+
+        impl<'a> ReadPgn for Window<'a> {
+            type Err = io::Error;
+
+            fn fill_buffer_and_peek(&mut self) -> io::Result<Option<u8>> {
+                Ok(self.buffer().first().cloned())
+            }
+
+            fn buffer(&self) -> &[u8] {
+                &self.bytes[self.pos..self.bytes.len().min(self.pos + self.size)]
+            }
+
+            fn consume(&mut self, n: usize) {
+                self.pos += n;
+            }
+
+            fn invalid_data() -> io::Error {
+                io::Error::from(io::ErrorKind::InvalidData)
+            }
+        }
+
+        for separator in [&b"\n\n"[..], b"\r\n\r\n", b"\n\r\n"] {
+            for padding in 0..32 {
+                let mut pgn = vec![b'x'; padding];
+                pgn.extend(separator);
+                pgn.extend(b"[Event \"B\"]");
+                let start = padding + separator.len();
+                for size in 12..24 {
+                    let mut window = Window {
+                        bytes: &pgn,
+                        pos: 0,
+                        size,
+                    };
+                    window.skip_to_game_start().unwrap();
+                    assert_eq!(window.pos, start, "padding {}, size {}", padding, size);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_limits() -> Result<(), io::Error> {
+        let mut pgn = b"1. e4 (1. d4 (1. c4)) *\n\n1. e4 e5 2. Nf3 *\n\n".to_vec();
+        pgn.extend(b"1. e4 ");
+        pgn.extend(vec![b'x'; 3 * MIN_BUFFER_SIZE]);
+        pgn.extend(b" *\n\n1. d4 *\n");
+
+        let limits = Limits {
+            max_game_bytes: Some(MIN_BUFFER_SIZE as u64),
+            max_depth: Some(1),
+            max_moves: Some(2),
+            ..Limits::default()
+        };
+
+        let mut counter = GameCounter::default();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        reader.set_limits(limits.clone());
+        let err = reader.read_game(&mut counter).unwrap_err();
+        assert_eq!(
+            LimitExceeded::from_io_error(&err),
+            Some(&LimitExceeded {
+                limit: Limit::Depth,
+                start: 0,
+            })
+        );
+        let err = reader.read_game(&mut counter).unwrap_err();
+        assert_eq!(
+            LimitExceeded::from_io_error(&err).map(|e| e.limit),
+            Some(Limit::Moves)
+        );
+        let err = reader.read_game(&mut counter).unwrap_err();
+        assert_eq!(
+            LimitExceeded::from_io_error(&err).map(|e| e.limit),
+            Some(Limit::GameBytes)
+        );
+        assert!(reader.read_game(&mut counter)?.is_some());
+        assert!(reader.read_game(&mut counter)?.is_none());
+        assert_eq!(counter.count, 4);
+        assert_eq!(reader.games_read(), 4);
+
+        let mut counter = GameCounter::default();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        reader.set_limits(Limits {
+            action: LimitAction::Skip,
+            ..limits
+        });
+        reader.read_all(&mut counter)?;
+        assert_eq!(counter.count, 4);
+        assert_eq!(reader.games_read(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_game_bytes_limit_in_buffer() -> Result<(), io::Error> {
+        #[derive(Default)]
+        struct VariationCounter {
+            begin: usize,
+            end: usize,
+        }
+
+        impl Visitor for VariationCounter {
+            type Result = ();
+
+            fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+                self.begin += 1;
+                Skip(false)
+            }
+            fn end_variation(&mut self) {
+                self.end += 1;
+            }
+            fn end_game(&mut self) {}
+        }
+
+        let mut pgn = Vec::new();
+        for _ in 0..2200 {
+            pgn.extend(b"1. e4 e5 *\n\n");
+        }
+        let start = pgn.len() as u64;
+        pgn.extend(b"1. e4 ( 1. d4 { ");
+        pgn.extend(vec![b'x'; 3600]);
+        pgn.extend(b" } d5 ) e5 *\n\n1. d4 *\n");
+
+        let mut counter = VariationCounter::default();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        reader.set_limits(Limits {
+            max_game_bytes: Some(1000),
+            ..Limits::default()
+        });
+        for _ in 0..2200 {
+            assert!(reader.read_game(&mut counter)?.is_some());
+        }
+        let err = reader.read_game(&mut counter).unwrap_err();
+        assert_eq!(
+            LimitExceeded::from_io_error(&err),
+            Some(&LimitExceeded {
+                limit: Limit::GameBytes,
+                start,
+            })
+        );
+        assert_eq!(counter.begin, 1);
+        assert_eq!(counter.end, 0);
+        assert!(reader.read_game(&mut counter)?.is_some());
+        assert!(reader.read_game(&mut counter)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_variations_and_plies() -> Result<(), io::Error> {
+        #[derive(Default)]
+        struct VariationCollector {
+            events: Vec<(usize, usize)>,
+            open: usize,
+            sans: Vec<(usize, String)>,
+        }
+
+        impl Visitor for VariationCollector {
+            type Result = ();
+
+            fn begin_variation(&mut self, depth: usize, ply: usize) -> Skip {
+                self.events.push((depth, ply));
+                self.open += 1;
+                Skip(depth > 2)
+            }
+
+            fn end_variation(&mut self) {
+                self.open -= 1;
+            }
+
+            fn san_at(&mut self, ply: usize, san_plus: SanPlus) {
+                self.sans.push((ply, san_plus.to_string()));
+            }
+
+            fn end_game(&mut self) {}
+        }
+
+        let mut collector = VariationCollector::default();
+        let mut reader = BufferedReader::new_cursor(
+            &b"1. e4 ) e5 (1... c5 2. Nf3 (2. Nc3 (2. f4 (2. d4))) 2... d6 (2... Nc6)) (1... e6 2. d4 *"[..],
+        );
+        reader.read_game(&mut collector)?;
+        assert_eq!(collector.events, [(1, 1), (2, 2), (3, 2), (2, 3), (1, 1)]);
+        assert_eq!(collector.open, 0);
+
+        let plies: Vec<_> = collector
+            .sans
+            .iter()
+            .map(|(ply, san)| (*ply, san.as_str()))
+            .collect();
+        assert_eq!(
+            plies,
+            [
+                (0, "e4"),
+                (1, "e5"),
+                (1, "c5"),
+                (2, "Nf3"),
+                (2, "Nc3"),
+                (3, "d6"),
+                (3, "Nc6"),
+                (1, "e6"),
+                (2, "d4"),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_nag() -> Result<(), io::Error> {
         struct NagCollector {
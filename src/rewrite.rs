@@ -0,0 +1,289 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{self, Read, Write};
+
+use crate::{
+    reader::{BufferedReader, ReadPgn, Tee},
+    types::{escape_header_value, RawHeader},
+    visitor::Visitor,
+};
+
+/// The headers of a game, as passed to the closure of
+/// [`BufferedReader::rewrite_headers()`](struct.BufferedReader.html#method.rewrite_headers).
+///
+/// Values are stored as in the source, with quotes and backslashes
+/// escaped.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Headers {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Headers {
+    /// Create an empty list of headers.
+    pub fn new() -> Headers {
+        Headers::default()
+    }
+
+    /// Returns the number of headers.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no headers.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the headers in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], RawHeader<'_>)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_slice(), RawHeader(value)))
+    }
+
+    /// Returns the value of the first header with the given key.
+    pub fn get(&self, key: &[u8]) -> Option<RawHeader<'_>> {
+        self.iter().find(|&(k, _)| k == key).map(|(_, value)| value)
+    }
+
+    /// Sets the value of the first header with the given key, or appends a
+    /// new header. Quotes and backslashes in `value` are escaped.
+    pub fn set(&mut self, key: &[u8], value: &[u8]) {
+        let escaped = escape(value);
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = escaped,
+            None => self.entries.push((key.to_vec(), escaped)),
+        }
+    }
+
+    /// Inserts a header at the given position. Quotes and backslashes in
+    /// `value` are escaped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len()`.
+    pub fn insert(&mut self, index: usize, key: &[u8], value: &[u8]) {
+        self.entries.insert(index, (key.to_vec(), escape(value)));
+    }
+
+    /// Removes all headers with the given key. Returns whether any header
+    /// was removed.
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|(k, _)| k != key);
+        self.entries.len() != len
+    }
+
+    /// Keeps only the headers for which `f` returns `true`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[u8], RawHeader<'_>) -> bool,
+    {
+        self.entries.retain(|(key, value)| f(key, RawHeader(value)));
+    }
+
+    /// Removes all headers.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W, eol: &[u8]) -> io::Result<()> {
+        for (key, value) in &self.entries {
+            writer.write_all(b"[")?;
+            writer.write_all(key)?;
+            writer.write_all(b" \"")?;
+            writer.write_all(value)?;
+            writer.write_all(b"\"]")?;
+            writer.write_all(eol)?;
+        }
+        Ok(())
+    }
+}
+
+fn escape(value: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(value.len());
+    escape_header_value(value, &mut escaped);
+    escaped
+}
+
+impl Visitor for Headers {
+    type Result = ();
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        self.entries.push((key.to_vec(), value.as_bytes().to_vec()));
+    }
+
+    fn end_game(&mut self) {}
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Rewrites the headers of all remaining games, copying everything else
+    /// verbatim to `writer`.
+    ///
+    /// For each game, `f` can modify, insert or delete headers. Unchanged
+    /// header sections are copied verbatim. Otherwise the `%` escape lines
+    /// of the section are kept, and the headers are written one per line,
+    /// like `[White "Deep Blue"]`, with the line endings of the original
+    /// section and followed by a blank line. Games that end up without
+    /// headers stay separated from the previous game.
+    ///
+    /// The movetext is copied verbatim. It is not parsed, but skipped with
+    /// the same fast path as a [`Visitor`] returning `Skip(true)` from
+    /// `end_headers()`.
+    ///
+    /// Returns the number of games.
+    ///
+    /// ```
+    /// use pgn_reader::BufferedReader;
+    ///
+    /// let pgn = b"[White \"Carlsen, M.\"]\n[Black \"Caruana, F.\"]\n\n1.e4   e5 {!} *\n";
+    ///
+    /// let mut out = Vec::new();
+    /// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+    /// reader.rewrite_headers(&mut out, |headers| {
+    ///     headers.set(b"White", b"?");
+    ///     headers.remove(b"Black");
+    ///     headers.set(b"Annotator", b"\"Anon\"");
+    /// })?;
+    ///
+    /// assert_eq!(
+    ///     out,
+    ///     &b"[White \"?\"]\n[Annotator \"\\\"Anon\\\"\"]\n\n1.e4   e5 {!} *\n"[..]
+    /// );
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader or from `writer`.
+    /// * Irrecoverable parser errors in the headers.
+    ///
+    /// [`Visitor`]: trait.Visitor.html
+    pub fn rewrite_headers<W, F>(&mut self, mut writer: W, mut f: F) -> io::Result<u64>
+    where
+        W: Write,
+        F: FnMut(&mut Headers),
+    {
+        let mut games = 0;
+        let mut headers = Headers::new();
+
+        let mut tee = Tee::new(self, &mut writer);
+        tee.skip_bom()?;
+        tee.skip_whitespace()?;
+        tee.check()?;
+
+        let mut section = Vec::new();
+
+        while tee.reader.has_more()? {
+            headers.clear();
+            section.clear();
+            Tee::new(&mut *tee.reader, &mut section).read_headers(&mut headers)?;
+            let original = headers.clone();
+            f(&mut headers);
+
+            if headers == original {
+                tee.writer.write_all(&section)?;
+            } else {
+                let eol: &[u8] = if section.windows(2).any(|w| w == b"\r\n") {
+                    b"\r\n"
+                } else {
+                    b"\n"
+                };
+
+                tee.fill_buffer_and_peek()?;
+                let blank_line = if tee.buffer().starts_with(b"\r\n") {
+                    2
+                } else if tee.buffer().starts_with(b"\n") {
+                    1
+                } else {
+                    0
+                };
+
+                if headers.is_empty() && games > 0 && blank_line == 0 {
+                    // Keep the separator from the previous game.
+                    tee.writer.write_all(eol)?;
+                }
+
+                for line in section.split_inclusive(|&ch| ch == b'\n') {
+                    if line.starts_with(b"%") {
+                        tee.writer.write_all(line)?;
+                        if !line.ends_with(b"\n") {
+                            tee.writer.write_all(eol)?;
+                        }
+                    }
+                }
+
+                headers.write_to(&mut tee.writer, eol)?;
+
+                if !headers.is_empty() && blank_line == 0 {
+                    tee.writer.write_all(eol)?;
+                } else if headers.is_empty() && games == 0 {
+                    // Nothing to separate from.
+                    tee.reader.consume(blank_line);
+                }
+            }
+
+            tee.skip_movetext()?;
+            tee.skip_whitespace()?;
+            tee.check()?;
+
+            tee.reader.game_done(true);
+            games += 1;
+        }
+
+        self.game_done(false);
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_headers() -> io::Result<()> {
+        let pgn = b"\n\n[Event \"A\"]\r\n% escaped\n\n1. e4\n; comment\n%{ kept\n*\n\n\n1. d4 *\n[Event \"C\"]\n1. c4";
+
+        let mut out = Vec::new();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let games = reader.rewrite_headers(&mut out, |headers| {
+            headers.insert(0, b"Event", b"X");
+            headers.retain(|_, value| value.as_bytes() != b"C");
+        })?;
+
+        assert_eq!(games, 3);
+        assert_eq!(reader.games_read(), 3);
+        assert_eq!(
+            out,
+            &b"\n\n% escaped\n[Event \"X\"]\r\n[Event \"A\"]\r\n\n1. e4\n; comment\n%{ kept\n*\n\n\n[Event \"X\"]\n\n1. d4 *\n[Event \"X\"]\n\n1. c4"[..]
+        );
+
+        let pgn = b"[Event \"A\"]\n\n1. d4 *\n[Event \"C\"]\n1. c4 *";
+        let mut out = Vec::new();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        reader.rewrite_headers(&mut out, |headers| {
+            headers.remove(b"Event");
+        })?;
+        assert_eq!(out, &b"1. d4 *\n\n1. c4 *"[..]);
+
+        let mut out = Vec::new();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        reader.rewrite_headers(&mut out, |_| ())?;
+        assert_eq!(out, &pgn[..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::{self, Read},
+    ops::Range,
+};
+
+use crate::{
+    reader::{BufferedReader, ReadPgn},
+    types::RawHeader,
+    visitor::Visitor,
+};
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Entry {
+    key: u32,
+    value: Range<usize>,
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Row {
+    span: Range<u64>,
+    entries: Range<usize>,
+}
+
+/// The headers of many games, stored compactly.
+///
+/// All keys and values share a single arena, and each distinct key is
+/// stored only once. Built by
+/// [`BufferedReader::scan_headers()`](struct.BufferedReader.html#method.scan_headers).
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct HeaderTable {
+    arena: Vec<u8>,
+    keys: Vec<Range<usize>>,
+    // Hashes of the keys in the arena.
+    key_ids: HashMap<u64, u32>,
+    entries: Vec<Entry>,
+    rows: Vec<Row>,
+}
+
+impl HeaderTable {
+    /// Create an empty table.
+    pub fn new() -> HeaderTable {
+        HeaderTable::default()
+    }
+
+    /// Returns the number of games.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns whether there are no games.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns the headers of the game with the given index.
+    pub fn get(&self, index: usize) -> Option<HeaderRow<'_>> {
+        self.rows
+            .get(index)
+            .map(|row| HeaderRow { table: self, row })
+    }
+
+    /// Iterates over the games.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = HeaderRow<'_>> {
+        self.rows
+            .iter()
+            .map(move |row| HeaderRow { table: self, row })
+    }
+
+    fn key(&self, key: u32) -> &[u8] {
+        &self.arena[self.keys[key as usize].clone()]
+    }
+
+    fn intern(&mut self, key: &[u8]) -> u32 {
+        let hash = hash_key(key);
+        match self.key_ids.get(&hash) {
+            Some(&id) if self.key(id) == key => return id,
+            Some(_) => {
+                // Hash collision.
+                if let Some(id) = (0..self.keys.len() as u32).find(|&id| self.key(id) == key) {
+                    return id;
+                }
+            }
+            None => (),
+        }
+        let id = self.keys.len() as u32;
+        let start = self.arena.len();
+        self.arena.extend_from_slice(key);
+        self.keys.push(start..self.arena.len());
+        self.key_ids.entry(hash).or_insert(id);
+        id
+    }
+
+    fn mark(&self) -> Mark {
+        Mark {
+            arena: self.arena.len(),
+            keys: self.keys.len(),
+            entries: self.entries.len(),
+        }
+    }
+
+    /// Removes everything added after `mark`.
+    fn truncate(&mut self, mark: Mark) {
+        for id in mark.keys as u32..self.keys.len() as u32 {
+            let hash = hash_key(self.key(id));
+            if self.key_ids.get(&hash) == Some(&id) {
+                self.key_ids.remove(&hash);
+            }
+        }
+        self.keys.truncate(mark.keys);
+        self.entries.truncate(mark.entries);
+        self.arena.truncate(mark.arena);
+    }
+}
+
+fn hash_key(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Sizes of a `HeaderTable`, to roll back a partially added game.
+#[derive(Clone, Copy, Debug)]
+struct Mark {
+    arena: usize,
+    keys: usize,
+    entries: usize,
+}
+
+/// The headers of a single game in a [`HeaderTable`](struct.HeaderTable.html).
+#[derive(Clone, Copy, Debug)]
+pub struct HeaderRow<'a> {
+    table: &'a HeaderTable,
+    row: &'a Row,
+}
+
+impl<'a> HeaderRow<'a> {
+    /// Returns the byte span of the game in the source, from the opening
+    /// `[` of the first header to the end of the movetext.
+    pub fn span(&self) -> Range<u64> {
+        self.row.span.clone()
+    }
+
+    /// Iterates over the headers in order.
+    pub fn headers(&self) -> impl Iterator<Item = (&'a [u8], RawHeader<'a>)> {
+        let table = self.table;
+        table.entries[self.row.entries.clone()]
+            .iter()
+            .map(move |entry| {
+                (
+                    table.key(entry.key),
+                    RawHeader(&table.arena[entry.value.clone()]),
+                )
+            })
+    }
+
+    /// Returns the value of the first header with the given key.
+    pub fn get(&self, key: &[u8]) -> Option<RawHeader<'a>> {
+        self.headers()
+            .find(|&(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Appends headers to the table.
+struct Builder<'a> {
+    table: &'a mut HeaderTable,
+}
+
+impl<'a> Visitor for Builder<'a> {
+    type Result = ();
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        let key = self.table.intern(key);
+        let start = self.table.arena.len();
+        self.table.arena.extend_from_slice(value.as_bytes());
+        self.table.entries.push(Entry {
+            key,
+            value: start..self.table.arena.len(),
+        });
+    }
+
+    fn end_game(&mut self) {}
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Reads only the headers of all remaining games into `table`, skipping
+    /// the movetext with the fastest available path. Returns the number of
+    /// games added.
+    ///
+    /// This does the same work as reading games with a [`Visitor`] that
+    /// returns `Skip(true)` from `end_headers()`, but stores the headers
+    /// compactly without allocating for each game.
+    ///
+    /// ```
+    /// use pgn_reader::{BufferedReader, HeaderTable};
+    ///
+    /// let pgn = b"[White \"Carlsen\"]\n\n1. e4 *\n\n[White \"Caruana\"]\n\n1. d4 *";
+    ///
+    /// let mut table = HeaderTable::new();
+    /// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+    /// assert_eq!(reader.scan_headers(&mut table)?, 2);
+    ///
+    /// let game = table.get(1).unwrap();
+    /// assert_eq!(game.get(b"White").unwrap().as_bytes(), b"Caruana");
+    /// assert_eq!(game.span(), 28..54);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors in the headers. Games before the error
+    ///   remain in the table.
+    ///
+    /// [`Visitor`]: trait.Visitor.html
+    pub fn scan_headers(&mut self, table: &mut HeaderTable) -> io::Result<u64> {
+        let mut games = 0;
+
+        while self.has_more()? {
+            let start = self.bytes_consumed();
+            let mark = table.mark();
+
+            let result = self
+                .read_headers(&mut Builder { table })
+                .and_then(|_| self.skip_movetext());
+            if let Err(err) = result {
+                table.truncate(mark);
+                return Err(self.damaged(start, err));
+            }
+
+            table.rows.push(Row {
+                span: start..self.bytes_consumed(),
+                entries: mark.entries..table.entries.len(),
+            });
+            self.game_done(true);
+            games += 1;
+        }
+
+        self.game_done(false);
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_headers() -> io::Result<()> {
+        let pgn = b"[Event \"A\"]\n[Site \"?\"]\n\n1. e4 *\n\n1. d4 *\n\n[Site \"x\"]\n[Event \"\\\"C\\\"\"]\n1. c4";
+
+        let mut table = HeaderTable::new();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        assert_eq!(reader.scan_headers(&mut table)?, 3);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.keys.len(), 2);
+
+        let rows: Vec<_> = table
+            .iter()
+            .map(|row| {
+                let span = row.span();
+                let headers: Vec<_> = row
+                    .headers()
+                    .map(|(key, value)| (key, value.decode().into_owned()))
+                    .collect();
+                (&pgn[span.start as usize..span.end as usize], headers)
+            })
+            .collect();
+
+        assert_eq!(
+            rows,
+            [
+                (
+                    &b"[Event \"A\"]\n[Site \"?\"]\n\n1. e4 *\n"[..],
+                    vec![(&b"Event"[..], b"A".to_vec()), (b"Site", b"?".to_vec())]
+                ),
+                (b"1. d4 *\n", vec![]),
+                (
+                    b"[Site \"x\"]\n[Event \"\\\"C\\\"\"]\n1. c4",
+                    vec![(&b"Site"[..], b"x".to_vec()), (b"Event", b"\"C\"".to_vec())]
+                ),
+            ]
+        );
+
+        // A damaged game leaves nothing behind.
+        let before = table.clone();
+        let mut reader = BufferedReader::new_cursor(&b"[Round \"1\"]\n[Board \"unterminated"[..]);
+        assert!(reader.scan_headers(&mut table).is_err());
+        assert_eq!(table, before);
+        Ok(())
+    }
+}
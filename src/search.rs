@@ -0,0 +1,488 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt,
+    io::{self, Read},
+    str::FromStr,
+};
+
+use shakmaty::{
+    fen::Fen,
+    san::SanPlus,
+    zobrist::{Zobrist64, ZobristHash},
+    Board, CastlingMode, Chess, Color, EnPassantMode, Move, Position, Role,
+};
+
+use crate::{
+    reader::BufferedReader,
+    types::{RawHeader, Skip},
+    visitor::Visitor,
+};
+
+const ROLES: [(Role, char); 6] = [
+    (Role::King, 'k'),
+    (Role::Queen, 'q'),
+    (Role::Rook, 'r'),
+    (Role::Bishop, 'b'),
+    (Role::Knight, 'n'),
+    (Role::Pawn, 'p'),
+];
+
+/// The number of pieces of each type and color, written as a signature
+/// like `KRPkr`, with uppercase letters for white pieces and lowercase
+/// letters for black pieces.
+///
+/// ```
+/// use pgn_reader::Material;
+///
+/// let material: Material = "KPRkr".parse()?;
+/// assert_eq!(material.to_string(), "KRPkr");
+/// # Ok::<_, pgn_reader::InvalidMaterial>(())
+/// ```
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Material {
+    // Indexed by color and role - 1.
+    counts: [[u8; 6]; 2],
+}
+
+impl Material {
+    /// Tries to parse a material signature from ASCII.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidMaterial`] error if the input contains anything
+    /// but piece letters, or too many pieces.
+    ///
+    /// [`InvalidMaterial`]: struct.InvalidMaterial.html
+    pub fn from_ascii(s: &[u8]) -> Result<Material, InvalidMaterial> {
+        let mut material = Material::default();
+        for &ch in s {
+            let role = Role::from_char(char::from(ch)).ok_or(InvalidMaterial { _priv: () })?;
+            let color = Color::from_white(ch.is_ascii_uppercase());
+            let count = &mut material.counts[color as usize][role as usize - 1];
+            if *count >= 64 {
+                return Err(InvalidMaterial { _priv: () });
+            }
+            *count += 1;
+        }
+        Ok(material)
+    }
+
+    /// Counts the pieces on a board.
+    pub fn from_board(board: &Board) -> Material {
+        let mut material = Material::default();
+        for color in Color::ALL {
+            for (role, _) in ROLES {
+                material.counts[color as usize][role as usize - 1] =
+                    (board.by_color(color) & board.by_role(role)).count() as u8;
+            }
+        }
+        material
+    }
+
+    /// Returns the number of pieces of the given color and type.
+    pub fn count(&self, color: Color, role: Role) -> u8 {
+        self.counts[color as usize][role as usize - 1]
+    }
+
+    /// Tests if a position with this material could still reach a
+    /// position with the `target` material. Pieces can only disappear,
+    /// except for pawns promoting to other pieces.
+    pub fn can_reach(&self, target: &Material) -> bool {
+        Color::ALL.into_iter().all(|color| {
+            let count = |material: &Material, role| material.count(color, role);
+            let spare_pawns = match count(self, Role::Pawn).checked_sub(count(target, Role::Pawn)) {
+                Some(spare_pawns) => spare_pawns,
+                None => return false,
+            };
+            let promotions: u32 = [Role::Knight, Role::Bishop, Role::Rook, Role::Queen]
+                .into_iter()
+                .map(|role| u32::from(count(target, role).saturating_sub(count(self, role))))
+                .sum();
+            count(target, Role::King) <= count(self, Role::King)
+                && promotions <= u32::from(spare_pawns)
+        })
+    }
+}
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for color in [Color::White, Color::Black] {
+            for (role, ch) in ROLES {
+                for _ in 0..self.count(color, role) {
+                    let ch = if color.is_white() {
+                        ch.to_ascii_uppercase()
+                    } else {
+                        ch
+                    };
+                    write!(f, "{}", ch)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Material {
+    type Err = InvalidMaterial;
+
+    fn from_str(s: &str) -> Result<Material, InvalidMaterial> {
+        Material::from_ascii(s.as_bytes())
+    }
+}
+
+/// Error when parsing an invalid material signature.
+#[derive(Clone, Eq, PartialEq)]
+pub struct InvalidMaterial {
+    _priv: (),
+}
+
+impl fmt::Debug for InvalidMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InvalidMaterial").finish()
+    }
+}
+
+impl fmt::Display for InvalidMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "invalid material signature".fmt(f)
+    }
+}
+
+impl Error for InvalidMaterial {}
+
+/// The targets of a position search with
+/// [`BufferedReader::search_positions()`](struct.BufferedReader.html#method.search_positions).
+///
+/// A game matches when it reaches any of the target positions, by any move
+/// order, or any of the target material signatures.
+#[derive(Clone, Debug, Default)]
+pub struct PositionSearch {
+    hashes: HashSet<u64>,
+    positions: Vec<Material>,
+    materials: Vec<Material>,
+}
+
+impl PositionSearch {
+    /// Create a search without targets.
+    pub fn new() -> PositionSearch {
+        PositionSearch::default()
+    }
+
+    /// Returns whether there are no targets.
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty() && self.materials.is_empty()
+    }
+
+    /// Adds a target position. Positions are compared by Zobrist hash,
+    /// including the side to move, castling rights and legal en passant
+    /// squares, but not move counters.
+    pub fn add_position<P: Position>(&mut self, pos: &P) {
+        if self.hashes.insert(hash(pos)) {
+            self.positions.push(Material::from_board(pos.board()));
+        }
+    }
+
+    /// Adds a target material signature.
+    pub fn add_material(&mut self, material: Material) {
+        self.materials.push(material);
+    }
+
+    fn matches(&self, pos: &Chess, material: &Material) -> bool {
+        self.materials.contains(material)
+            || (self.positions.contains(material) && self.hashes.contains(&hash(pos)))
+    }
+
+    fn can_match(&self, material: &Material) -> bool {
+        self.positions
+            .iter()
+            .chain(&self.materials)
+            .any(|target| material.can_reach(target))
+    }
+}
+
+pub(crate) fn hash<P: Position>(pos: &P) -> u64 {
+    pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0
+}
+
+/// Replays the mainline of a game, starting from the position in the `FEN`
+/// header, if any. Games of variants other than standard chess and
+/// Chess960 are not replayed.
+#[derive(Clone)]
+pub(crate) struct Replay {
+    pub(crate) pos: Chess,
+    pub(crate) ply: usize,
+    pub(crate) done: bool,
+    pub(crate) castling_mode: CastlingMode,
+}
+
+impl Replay {
+    pub(crate) fn new() -> Replay {
+        Replay {
+            pos: Chess::default(),
+            ply: 0,
+            done: false,
+            castling_mode: CastlingMode::Standard,
+        }
+    }
+
+    pub(crate) fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        if key == b"FEN" {
+            match Fen::from_ascii(value.as_bytes())
+                .ok()
+                .and_then(|fen| fen.into_position(CastlingMode::Chess960).ok())
+            {
+                Some(pos) => self.pos = pos,
+                None => self.done = true,
+            }
+        } else if key == b"Variant" {
+            let variant: Vec<u8> = value
+                .as_bytes()
+                .iter()
+                .filter(|ch| ch.is_ascii_alphanumeric())
+                .map(u8::to_ascii_lowercase)
+                .collect();
+            match &variant[..] {
+                b"standard" | b"fromposition" => (),
+                // Standard rules, but castling from any starting position.
+                b"chess960" | b"fischerandom" | b"fischerrandom" => {
+                    self.castling_mode = CastlingMode::Chess960
+                }
+                _ => self.done = true,
+            }
+        }
+    }
+
+    /// Plays the move, unless replaying is done. Replaying is done after
+    /// the first illegal move.
+    pub(crate) fn play(&mut self, san_plus: &SanPlus) -> Option<Move> {
+        if self.done {
+            return None;
+        }
+        match san_plus.san.to_move(&self.pos) {
+            Ok(m) => {
+                self.pos.play_unchecked(m);
+                self.ply += 1;
+                Some(m)
+            }
+            Err(_) => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+/// A game that matched a [`PositionSearch`](struct.PositionSearch.html).
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct PositionHit {
+    /// Number of games read before the matching game, like
+    /// [`BufferedReader::games_read()`](struct.BufferedReader.html#method.games_read).
+    pub game: u64,
+    /// Number of plies played in the mainline before the first match.
+    /// `0` if the starting position matches.
+    pub ply: usize,
+    /// Byte offset of the start of the game.
+    pub offset: u64,
+}
+
+struct Searcher<'a> {
+    search: &'a PositionSearch,
+    replay: Replay,
+    hit: Option<usize>,
+}
+
+impl<'a> Searcher<'a> {
+    fn check(&mut self) {
+        let material = Material::from_board(self.replay.pos.board());
+        if self.search.matches(&self.replay.pos, &material) {
+            self.hit = Some(self.replay.ply);
+            self.replay.done = true;
+        } else if !self.search.can_match(&material) {
+            // Stop replaying moves as early as possible.
+            self.replay.done = true;
+        }
+    }
+}
+
+impl<'a> Visitor for Searcher<'a> {
+    type Result = Option<usize>;
+
+    fn begin_game(&mut self) {
+        self.replay = Replay::new();
+        self.hit = None;
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        self.replay.header(key, value);
+    }
+
+    fn end_headers(&mut self) -> Skip {
+        if !self.replay.done {
+            self.check();
+        }
+        Skip(self.replay.done)
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        if self.replay.play(&san_plus).is_some() {
+            self.check();
+        }
+    }
+
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
+        Skip(true) // stay in the mainline
+    }
+
+    fn end_game(&mut self) -> Option<usize> {
+        self.hit
+    }
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Replays the mainlines of all remaining games and calls `f` for each
+    /// game that matches the search. Returns the number of games searched.
+    ///
+    /// Games with a `FEN` header start from the given position. Replaying a
+    /// game stops at the first match, and as soon as its material can no
+    /// longer reach any target. Games of other variants and games with
+    /// illegal moves are never reported after the point of the error.
+    ///
+    /// ```
+    /// use pgn_reader::{BufferedReader, PositionSearch};
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess};
+    ///
+    /// let pgn = b"1. Nf3 Nf6 2. d4 d5 *\n\n1. d4 d5 2. Nf3 Nf6 *\n\n1. e4 e5 *\n";
+    ///
+    /// let target: Chess = "rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 2 3"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// let mut search = PositionSearch::new();
+    /// search.add_position(&target);
+    ///
+    /// let mut hits = Vec::new();
+    /// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+    /// reader.search_positions(&search, |hit| hits.push((hit.game, hit.ply, hit.offset)))?;
+    /// assert_eq!(hits, [(0, 4, 0), (1, 4, 23)]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub fn search_positions<F>(&mut self, search: &PositionSearch, mut f: F) -> io::Result<u64>
+    where
+        F: FnMut(PositionHit),
+    {
+        let mut searcher = Searcher {
+            search,
+            replay: Replay::new(),
+            hit: None,
+        };
+
+        let mut games = 0;
+        while self.has_more()? {
+            let game = self.games_read();
+            let offset = self.bytes_consumed();
+            if let Some(Some(ply)) = self.read_game(&mut searcher)? {
+                f(PositionHit { game, ply, offset });
+            }
+            games += 1;
+        }
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material() {
+        let material: Material = "KRPPkrp".parse().unwrap();
+        assert!(material.can_reach(&"KRPkr".parse().unwrap()));
+        assert!(material.can_reach(&"KQRkr".parse().unwrap()));
+        assert!(!material.can_reach(&"KQRPPkr".parse().unwrap()));
+        assert!(!material.can_reach(&"KRPkrpp".parse().unwrap()));
+        assert!(Material::from_ascii(b"KRx").is_err());
+        assert_eq!(
+            Material::from_board(&Board::default()).to_string(),
+            "KQRRBBNNPPPPPPPPkqrrbbnnpppppppp"
+        );
+    }
+
+    #[test]
+    fn test_search_materials() -> io::Result<()> {
+        let pgn = b"[FEN \"r3k3/8/8/8/8/8/4P3/R3K3 w - - 0 1\"]\n\n1. Rxa8+ Kd7 2. Ra7+ *\n\n[FEN \"r3k3/8/8/8/8/8/4P3/R3K3 w - - 0 1\"]\n\n1. Kf2 Rxa1 2. e4 Rb1 *\n";
+
+        let mut search = PositionSearch::new();
+        search.add_material("KRPk".parse().unwrap());
+        search.add_material("KPkr".parse().unwrap());
+
+        let mut hits = Vec::new();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        assert_eq!(reader.search_positions(&search, |hit| hits.push(hit))?, 2);
+        assert_eq!(
+            hits,
+            [
+                PositionHit {
+                    game: 0,
+                    ply: 1,
+                    offset: 0
+                },
+                PositionHit {
+                    game: 1,
+                    ply: 2,
+                    offset: 67
+                }
+            ]
+        );
+
+        // Chess960 castling.
+        let fen = "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1";
+        let mut pos: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Chess960)
+            .unwrap();
+        let m = "O-O-O"
+            .parse::<SanPlus>()
+            .unwrap()
+            .san
+            .to_move(&pos)
+            .unwrap();
+        pos.play_unchecked(m);
+        let mut search = PositionSearch::new();
+        search.add_position(&pos);
+        let pgn = format!("[Variant \"Chess960\"]\n[FEN \"{fen}\"]\n\n1. O-O-O *\n");
+        let mut hits = Vec::new();
+        let mut reader = BufferedReader::new_cursor(pgn.as_bytes());
+        reader.search_positions(&search, |hit| hits.push(hit.ply))?;
+        assert_eq!(hits, [1]);
+
+        // Unreachable without promoting the only pawn twice.
+        let mut search = PositionSearch::new();
+        search.add_material("KQRRkr".parse().unwrap());
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        reader.search_positions(&search, |_| panic!("unexpected hit"))?;
+        Ok(())
+    }
+}
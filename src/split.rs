@@ -0,0 +1,233 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{self, Read};
+
+use crate::{
+    reader::{BufferedReader, ReadPgn, SliceReader, Tee},
+    visitor::{SkipVisitor, Visitor},
+};
+
+/// Skips whitespace, but stops at `%` escape lines, which belong to the
+/// following game.
+fn skip_blank<P: ReadPgn>(reader: &mut P) -> Result<(), P::Err> {
+    while let Some(b' ' | b'\t' | b'\r' | b'\n') = reader.fill_buffer_and_peek()? {
+        reader.bump();
+    }
+    Ok(())
+}
+
+/// Whether the error is a parser error, as opposed to an I/O error.
+fn is_parser_error(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::InvalidData && err.get_ref().is_none()
+}
+
+/// Splits PGN in memory into the raw bytes of each game, without
+/// interpreting the moves.
+///
+/// Games are delimited exactly like
+/// [`BufferedReader::skip_game()`](struct.BufferedReader.html#method.skip_game)
+/// does. Each slice contains the headers and the movetext, including the
+/// line break that ends the movetext, but not the whitespace between
+/// games. `%` escape lines before a game are part of its slice, while
+/// escape lines after the last game are dropped. Malformed games are
+/// returned like any other.
+///
+/// ```
+/// use pgn_reader::RawGames;
+///
+/// let pgn = b"[Event \"A\"]\n\n1. e4 *\n\n[Event \"B\"]\n\n1. d4 *";
+///
+/// let games: Vec<&[u8]> = RawGames::new(pgn).collect();
+/// assert_eq!(games, [&b"[Event \"A\"]\n\n1. e4 *\n"[..], b"[Event \"B\"]\n\n1. d4 *"]);
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct RawGames<'a> {
+    pgn: &'a [u8],
+    reader: SliceReader<'a>,
+}
+
+impl<'a> RawGames<'a> {
+    /// Create a splitter over the given bytes.
+    pub fn new(pgn: &'a [u8]) -> RawGames<'a> {
+        RawGames {
+            pgn,
+            reader: SliceReader::new(pgn),
+        }
+    }
+
+    /// Returns the offset of the next game, or of the end of the input.
+    pub fn offset(&mut self) -> usize {
+        // Cannot fail when reading from a slice.
+        let _ = self.skip_separator();
+        self.reader.consumed()
+    }
+
+    fn skip_separator(&mut self) -> io::Result<()> {
+        self.reader.skip_bom()?;
+        skip_blank(&mut self.reader)?;
+
+        // Drop escape lines that are not followed by another game.
+        let mut lookahead = self.reader.clone();
+        lookahead.skip_whitespace()?;
+        if lookahead.consumed() == self.pgn.len() {
+            self.reader = lookahead;
+        }
+        Ok(())
+    }
+
+    fn skip_game(&mut self) -> io::Result<()> {
+        self.reader.read_headers(&mut SkipVisitor)?;
+        self.reader.skip_movetext()
+    }
+}
+
+impl<'a> Iterator for RawGames<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let start = self.offset();
+        if start == self.pgn.len() {
+            return None;
+        }
+        // Parser errors end the game like anything else.
+        let _ = self.skip_game();
+        Some(&self.pgn[start..self.reader.consumed()])
+    }
+}
+
+/// Splits the games of a [`BufferedReader`] into owned raw bytes. Returned
+/// by
+/// [`BufferedReader::raw_games()`](struct.BufferedReader.html#method.raw_games).
+///
+/// Games are delimited like by [`RawGames`], and count towards
+/// [`BufferedReader::games_read()`](struct.BufferedReader.html#method.games_read).
+///
+/// [`BufferedReader`]: struct.BufferedReader.html
+/// [`RawGames`]: struct.RawGames.html
+#[derive(Debug)]
+#[must_use]
+pub struct OwnedRawGames<'a, R> {
+    reader: &'a mut BufferedReader<R>,
+}
+
+impl<'a, R: Read> OwnedRawGames<'a, R> {
+    /// Returns the next game like `next()`, passing its headers to
+    /// `visitor` on the way, so that games can be selected by their headers
+    /// without parsing them again. Only
+    /// [`Visitor::header()`](trait.Visitor.html#method.header) is called.
+    pub fn next_with_headers<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Option<io::Result<Vec<u8>>> {
+        self.read_game(visitor).transpose()
+    }
+
+    fn read_game<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<Option<Vec<u8>>> {
+        self.reader.skip_bom()?;
+        skip_blank(&mut *self.reader)?;
+
+        let mut game = Vec::new();
+        let mut tee = Tee::new(&mut *self.reader, &mut game);
+        tee.skip_whitespace()?;
+        if tee.fill_buffer_and_peek()?.is_none() {
+            // Only escape lines were left.
+            self.reader.game_done(false);
+            return Ok(None);
+        }
+
+        let result = tee.read_headers(visitor).and_then(|_| tee.skip_movetext());
+        match result {
+            Err(err) if !is_parser_error(&err) => return Err(err),
+            _ => tee.check()?,
+        }
+
+        self.reader.game_done(true);
+        Ok(Some(game))
+    }
+}
+
+impl<'a, R: Read> Iterator for OwnedRawGames<'a, R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        self.read_game(&mut SkipVisitor).transpose()
+    }
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Returns an iterator over the raw bytes of the remaining games,
+    /// without interpreting the moves. This is much faster than reading
+    /// games with a [`Visitor`](trait.Visitor.html).
+    ///
+    /// ```
+    /// use pgn_reader::BufferedReader;
+    ///
+    /// let pgn = b"1. e4 *\n\n\n1. d4 *\n";
+    ///
+    /// let mut reader = BufferedReader::new_cursor(&pgn[..]);
+    /// let games = reader.raw_games().collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(games, [b"1. e4 *\n", b"1. d4 *\n"]);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    pub fn raw_games(&mut self) -> OwnedRawGames<'_, R> {
+        OwnedRawGames { reader: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rewrite::Headers;
+
+    #[test]
+    fn test_slice_and_owned_agree() -> io::Result<()> {
+        let pgn = b"\xef\xbb\xbf% escaped\n[Event \"A\"]\n% escaped\n\n1. e4 { \n\n } *\n\n% before B\n[Event \"B\"]\n1. d4\n[Event \"C\"]\n\n1. c4 { unterminated\n\n  ";
+
+        let slices: Vec<&[u8]> = RawGames::new(pgn).collect();
+        assert_eq!(
+            slices,
+            [
+                &b"% escaped\n[Event \"A\"]\n% escaped\n\n1. e4 { \n\n } *\n"[..],
+                b"% before B\n[Event \"B\"]\n1. d4\n",
+                b"[Event \"C\"]\n\n1. c4 { unterminated\n\n  ",
+            ]
+        );
+
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let owned = reader.raw_games().collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(owned, slices);
+        assert_eq!(reader.games_read(), 3);
+
+        let mut headers = Headers::new();
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let game = reader
+            .raw_games()
+            .next_with_headers(&mut headers)
+            .unwrap()?;
+        assert_eq!(game, slices[0]);
+        assert_eq!(headers.get(b"Event").unwrap().as_bytes(), b"A");
+
+        // Trailing escape lines do not make a game.
+        let pgn = b"1. e4 *\n\n% trailing\n\n";
+        assert_eq!(RawGames::new(pgn).collect::<Vec<_>>(), [b"1. e4 *\n"]);
+        let mut reader = BufferedReader::new_cursor(&pgn[..]);
+        let owned = reader.raw_games().collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(owned, [b"1. e4 *\n"]);
+        Ok(())
+    }
+}
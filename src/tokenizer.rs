@@ -0,0 +1,530 @@
+// This file is part of the pgn-reader library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::VecDeque, io, ops::Range};
+
+use shakmaty::Outcome;
+
+use crate::reader::{is_token_end, MovetextToken, ReadPgn, SliceReader};
+
+/// Kind of a [`Token`](struct.Token.html).
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum TokenKind {
+    /// UTF-8 byte order mark at the start of the input.
+    Bom,
+    /// Spaces, tabs and line breaks.
+    Whitespace,
+    /// A line starting with `%`, excluding the line break.
+    Escape,
+    /// A `; comment`, excluding the line break.
+    LineComment,
+    /// `[` opening a header.
+    TagOpen,
+    /// Key of a header, like `White`.
+    TagName,
+    /// Value of a header, including the quotes, like `"Deep Blue"`. The
+    /// closing quote is missing if the value is unterminated.
+    TagValue,
+    /// `]` closing a header.
+    TagClose,
+    /// Move number, like `1.` or `12...`.
+    MoveNumber,
+    /// A move, like `Nf3+`.
+    San,
+    /// Numeric annotation glyph, like `!?` or `$7`.
+    Nag,
+    /// A `{ comment }`, including the braces.
+    Comment,
+    /// `(`.
+    BeginVariation,
+    /// `)`.
+    EndVariation,
+    /// Game termination, like `*` or `1-0`.
+    Result,
+    /// A token that the reader ignores or rejects, like an unterminated
+    /// comment.
+    Unknown,
+}
+
+/// A lexical token with its byte span.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Token {
+    /// The kind of token.
+    pub kind: TokenKind,
+    /// Byte range of the token in the input.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum State {
+    Start,
+    Headers,
+    Movetext,
+    Done,
+}
+
+/// Splits PGN into lexical tokens, using the same lexing primitives as
+/// [`BufferedReader`](struct.BufferedReader.html).
+///
+/// Unlike the [`Visitor`](trait.Visitor.html) API, every byte of the input
+/// is covered by exactly one token, including whitespace, move numbers and
+/// tokens that the reader ignores. The spans are contiguous.
+///
+/// Headers, moves, annotations, comments, variations, results and game
+/// boundaries are exactly the ones the reader reports. The bytes the reader
+/// skips in between are only classified for convenience.
+///
+/// ```
+/// use pgn_reader::{TokenKind, Tokenizer};
+///
+/// let pgn = b"[Event \"?\"]\n\n1. e4 $1 *";
+///
+/// let kinds: Vec<_> = Tokenizer::new(pgn).map(|token| token.kind).collect();
+/// assert_eq!(kinds, [
+///     TokenKind::TagOpen,
+///     TokenKind::TagName,
+///     TokenKind::Whitespace,
+///     TokenKind::TagValue,
+///     TokenKind::TagClose,
+///     TokenKind::Whitespace,
+///     TokenKind::MoveNumber,
+///     TokenKind::Whitespace,
+///     TokenKind::San,
+///     TokenKind::Whitespace,
+///     TokenKind::Nag,
+///     TokenKind::Whitespace,
+///     TokenKind::Result,
+/// ]);
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Tokenizer<'a> {
+    pgn: &'a [u8],
+    reader: SliceReader<'a>,
+    state: State,
+    /// Start of the bytes that were skipped by the reader, but not yet
+    /// emitted as tokens.
+    trivia: usize,
+    queue: VecDeque<(Token, bool)>,
+    game_end: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Create a tokenizer over the given bytes.
+    pub fn new(pgn: &'a [u8]) -> Tokenizer<'a> {
+        Tokenizer {
+            pgn,
+            reader: SliceReader::new(pgn),
+            state: State::Start,
+            trivia: 0,
+            queue: VecDeque::new(),
+            game_end: false,
+        }
+    }
+
+    /// Whether the most recently returned token ended a game, i.e. the
+    /// reader would continue with the next game after it. This is the case
+    /// for the last token of each game that is followed by another game.
+    pub fn ends_game(&self) -> bool {
+        self.game_end
+    }
+
+    fn pos(&self) -> usize {
+        self.reader.consumed()
+    }
+
+    fn push(&mut self, kind: TokenKind, span: Range<usize>) {
+        debug_assert_eq!(span.start, self.trivia);
+        debug_assert!(span.start < span.end);
+        self.trivia = span.end;
+
+        if let Some((last, _)) = self.queue.back_mut() {
+            if kind == TokenKind::Whitespace && last.kind == TokenKind::Whitespace {
+                last.span.end = span.end;
+                return;
+            }
+        }
+
+        self.queue.push_back((Token { kind, span }, false));
+    }
+
+    /// Emits the bytes skipped by the reader up to `end`.
+    fn push_trivia(&mut self, end: usize, movetext: bool) {
+        while self.trivia < end {
+            let start = self.trivia;
+            let rest = &self.pgn[start..end];
+            let until = |needle: &[u8]| {
+                rest.iter()
+                    .position(|ch| needle.contains(ch))
+                    .unwrap_or(rest.len())
+            };
+            let token_end = rest[1..]
+                .iter()
+                .position(|&ch| is_token_end(ch))
+                .map_or(rest.len(), |n| n + 1);
+            let at_line_start = start == 0 || self.pgn[start - 1] == b'\n';
+
+            let (kind, len) = match rest[0] {
+                b' ' | b'\t' | b'\r' | b'\n' => (
+                    TokenKind::Whitespace,
+                    rest.iter()
+                        .position(|ch| !matches!(ch, b' ' | b'\t' | b'\r' | b'\n'))
+                        .unwrap_or(rest.len()),
+                ),
+                b'%' if !movetext || at_line_start => (TokenKind::Escape, until(b"\n")),
+                b';' if movetext => (TokenKind::LineComment, until(b"\n")),
+                b']' => (TokenKind::TagClose, 1),
+                // Malformed header line.
+                b'[' if !movetext => (TokenKind::Unknown, until(b"\n]").max(1)),
+                // Unterminated comment.
+                b'{' if movetext => (TokenKind::Unknown, rest.len()),
+                b'.' => (
+                    TokenKind::MoveNumber,
+                    rest.iter().position(|&ch| ch != b'.').unwrap_or(rest.len()),
+                ),
+                b'0'..=b'9' if rest[..token_end].iter().all(u8::is_ascii_digit) => (
+                    TokenKind::MoveNumber,
+                    rest[token_end..]
+                        .iter()
+                        .position(|&ch| ch != b'.')
+                        .map_or(rest.len(), |n| token_end + n),
+                ),
+                _ => (TokenKind::Unknown, token_end),
+            };
+
+            self.push(kind, start..start + len);
+        }
+    }
+
+    /// Start of the token that the reader just finished at the current
+    /// position, by scanning back to the previous token end.
+    fn scan_back(&self) -> usize {
+        let mut start = self.pos();
+        while start > self.trivia && !is_token_end(self.pgn[start - 1]) {
+            start -= 1;
+        }
+        start
+    }
+
+    fn end_game(&mut self) {
+        if self.reader.remaining() > 0 {
+            if let Some((_, game_end)) = self.queue.back_mut() {
+                *game_end = true;
+            }
+        }
+        self.state = State::Start;
+    }
+
+    /// Emits the rest of the input after a parser error. The reader has
+    /// already skipped it.
+    fn fail(&mut self, movetext: bool) {
+        self.reader.consume_all();
+        self.push_trivia(self.pgn.len(), movetext);
+        self.end_game();
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        let before = self.pos();
+        self.reader.skip_bom()?;
+        if self.pos() > before {
+            self.push(TokenKind::Bom, before..self.pos());
+        }
+
+        self.reader.skip_whitespace()?;
+        self.push_trivia(self.pos(), false);
+
+        self.state = if self.reader.remaining() > 0 {
+            State::Headers
+        } else {
+            State::Done
+        };
+        Ok(())
+    }
+
+    fn headers(&mut self) -> io::Result<()> {
+        let header = match self.reader.read_header()? {
+            Some(header) => header,
+            None => {
+                self.push_trivia(self.pos(), false);
+                self.state = State::Movetext;
+                return Ok(());
+            }
+        };
+
+        // The reader is now positioned after the opening bracket.
+        let pos = self.pos();
+        self.push_trivia(pos - 1, false);
+        self.push(TokenKind::TagOpen, pos - 1..pos);
+        if header.key_end > 0 {
+            self.push(TokenKind::TagName, pos..pos + header.key_end);
+        }
+        if header.value_start - 1 > header.key_end {
+            self.push(
+                TokenKind::Whitespace,
+                pos + header.key_end..pos + header.value_start - 1,
+            );
+        }
+        self.push(
+            TokenKind::TagValue,
+            pos + header.value_start - 1..pos + header.len,
+        );
+
+        self.reader.end_header(&header)?;
+        self.push_trivia(self.pos(), false);
+        Ok(())
+    }
+
+    fn movetext(&mut self) -> io::Result<()> {
+        let token = match self.reader.read_token()? {
+            Some(token) => token,
+            None => {
+                self.push_trivia(self.pos(), true);
+                self.reader.skip_whitespace()?;
+                self.push_trivia(self.pos(), false);
+                self.end_game();
+                return Ok(());
+            }
+        };
+
+        let pos = self.pos();
+        let (kind, start) = match token {
+            MovetextToken::San(_) => (TokenKind::San, self.scan_back()),
+            MovetextToken::Nag(nag) => match self.pgn[pos - 1] {
+                b'!' | b'?' if nag.0 <= 2 => (TokenKind::Nag, pos - 1),
+                b'!' | b'?' => (TokenKind::Nag, pos - 2),
+                _ => (TokenKind::Nag, self.scan_back() - 1),
+            },
+            MovetextToken::Comment(len) => {
+                self.reader.consume(len + 1);
+                (TokenKind::Comment, pos - 1)
+            }
+            MovetextToken::BeginVariation => (TokenKind::BeginVariation, pos - 1),
+            MovetextToken::EndVariation => (TokenKind::EndVariation, pos - 1),
+            MovetextToken::Outcome(None) => (TokenKind::Result, pos - 1),
+            MovetextToken::Outcome(Some(Outcome::Decisive { .. })) => (TokenKind::Result, pos - 3),
+            MovetextToken::Outcome(Some(Outcome::Draw)) => (TokenKind::Result, pos - 7),
+        };
+
+        self.push_trivia(start, true);
+        self.push(kind, start..self.pos());
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        loop {
+            // Hold back trailing whitespace, so that it can be merged with
+            // whitespace skipped in the next step.
+            let ready = match self.queue.back() {
+                Some((last, _)) => {
+                    self.queue.len() > 1
+                        || last.kind != TokenKind::Whitespace
+                        || self.state == State::Done
+                }
+                None => false,
+            };
+            if ready {
+                let (token, game_end) = self.queue.pop_front().expect("non-empty queue");
+                self.game_end = game_end;
+                return Some(token);
+            }
+
+            let res = match self.state {
+                State::Start => self.start(),
+                State::Headers => self.headers(),
+                State::Movetext => self.movetext(),
+                State::Done => {
+                    self.game_end = false;
+                    return None;
+                }
+            };
+
+            if res.is_err() {
+                let movetext = self.state == State::Movetext;
+                self.fail(movetext);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        reader::BufferedReader,
+        types::{RawComment, RawHeader},
+        visitor::Visitor,
+    };
+    use shakmaty::san::SanPlus;
+
+    fn tokens(pgn: &[u8]) -> Vec<(TokenKind, &[u8])> {
+        let mut pos = 0;
+        Tokenizer::new(pgn)
+            .map(|token| {
+                assert_eq!(token.span.start, pos);
+                assert!(token.span.end > pos);
+                pos = token.span.end;
+                (token.kind, &pgn[token.span])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        use TokenKind::*;
+
+        let pgn = b"\xef\xbb\xbf[White \"A \\\"B\\\"\"]\r\n% escaped\n\n1.e4!? {c} 1... 0-0+ (1. Pe4 $12 xyz) ; rest\n1/2-1/2\n\n[Event \"2\"]\n1. d4";
+        assert_eq!(
+            tokens(pgn),
+            [
+                (Bom, &b"\xef\xbb\xbf"[..]),
+                (TagOpen, b"["),
+                (TagName, b"White"),
+                (Whitespace, b" "),
+                (TagValue, b"\"A \\\"B\\\"\""),
+                (TagClose, b"]"),
+                (Whitespace, b"\r\n"),
+                (Escape, b"% escaped"),
+                (Whitespace, b"\n\n"),
+                (MoveNumber, b"1."),
+                (San, b"e4"),
+                (Nag, b"!?"),
+                (Whitespace, b" "),
+                (Comment, b"{c}"),
+                (Whitespace, b" "),
+                (MoveNumber, b"1..."),
+                (Whitespace, b" "),
+                (San, b"0-0"),
+                (Unknown, b"+"),
+                (Whitespace, b" "),
+                (BeginVariation, b"("),
+                (MoveNumber, b"1."),
+                (Whitespace, b" "),
+                (San, b"Pe4"),
+                (Whitespace, b" "),
+                (Nag, b"$12"),
+                (Whitespace, b" "),
+                (Unknown, b"xyz"),
+                (EndVariation, b")"),
+                (Whitespace, b" "),
+                (LineComment, b"; rest"),
+                (Whitespace, b"\n"),
+                (Result, b"1/2-1/2"),
+                (Whitespace, b"\n\n"),
+                (TagOpen, b"["),
+                (TagName, b"Event"),
+                (Whitespace, b" "),
+                (TagValue, b"\"2\""),
+                (TagClose, b"]"),
+                (Whitespace, b"\n"),
+                (MoveNumber, b"1."),
+                (Whitespace, b" "),
+                (San, b"d4"),
+            ]
+        );
+    }
+
+    struct Collector {
+        games: Vec<Vec<String>>,
+    }
+
+    impl Visitor for Collector {
+        type Result = ();
+
+        fn begin_game(&mut self) {
+            self.games.push(Vec::new());
+        }
+
+        fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+            self.games.last_mut().unwrap().push(format!(
+                "{}={}",
+                String::from_utf8_lossy(key),
+                String::from_utf8_lossy(value.as_bytes())
+            ));
+        }
+
+        fn san(&mut self, san_plus: SanPlus) {
+            self.games.last_mut().unwrap().push(san_plus.to_string());
+        }
+
+        fn comment(&mut self, comment: RawComment<'_>) {
+            self.games.last_mut().unwrap().push(format!(
+                "{{{}}}",
+                String::from_utf8_lossy(comment.as_bytes())
+            ));
+        }
+
+        fn end_game(&mut self) {}
+    }
+
+    fn reader_games(pgn: &[u8]) -> Vec<Vec<String>> {
+        let mut collector = Collector { games: Vec::new() };
+        let mut reader = BufferedReader::new_cursor(pgn);
+        while reader.read_game(&mut collector).unwrap().is_some() {}
+        collector.games
+    }
+
+    fn tokenizer_games(pgn: &[u8]) -> Vec<Vec<String>> {
+        let mut games = vec![Vec::new()];
+        let mut tokenizer = Tokenizer::new(pgn);
+        let mut key = None;
+        while let Some(token) = tokenizer.next() {
+            let text = String::from_utf8_lossy(&pgn[token.span.clone()]).into_owned();
+            match token.kind {
+                TokenKind::TagName => key = Some(text),
+                TokenKind::TagValue => games.last_mut().unwrap().push(format!(
+                    "{}={}",
+                    key.take().unwrap_or_default(),
+                    text.trim_start_matches('"').trim_end_matches('"')
+                )),
+                TokenKind::San | TokenKind::Comment => games.last_mut().unwrap().push(text),
+                _ => (),
+            }
+            if tokenizer.ends_game() {
+                games.push(Vec::new());
+            }
+        }
+        games
+    }
+
+    #[test]
+    fn test_agrees_with_reader() {
+        for pgn in &[
+            &b"[A \"1\"]\n  [B \"2\"]\n\n1. e4 *"[..],
+            b"[Event \"x\"] ]\n[Bad\n\n1.e4 e5 ;c\n2.Nf3 }stray\n\n\n[Event \"y\"]\n1... ??? xyz $300",
+            b"% header\n[White \"A\"]\n% escaped\n[Black \"B\"]\n1. d4 { a } (1. c4 \r\n%x\n) d5\n[Event \"?\"]\n\n\n1. Nf3 \r\n\r\n1. g3 \n",
+            b"[A \"1\"][B\"2\"]\n1. e4 [C \"3\"]\n\n[D \"4\n1. e4",
+        ] {
+            assert_eq!(tokenizer_games(pgn), reader_games(pgn));
+        }
+    }
+
+    #[test]
+    fn test_ends_game() {
+        let pgn = b"1. e4\n[Event \"?\"]\n1. d4 \r\n\r\n1. c4";
+        let mut tokenizer = Tokenizer::new(pgn);
+        let mut ends = Vec::new();
+        while let Some(token) = tokenizer.next() {
+            if tokenizer.ends_game() {
+                ends.push(token.span.end);
+            }
+        }
+        assert_eq!(ends, [6, 28]);
+    }
+}
@@ -26,6 +26,13 @@ use std::{
 #[must_use]
 pub struct Skip(pub bool);
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Skip {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(self.0)
+    }
+}
+
 /// A numeric annotation glyph like `?`, `!!` or `$42`.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct Nag(pub u8);
@@ -103,6 +110,14 @@ impl From<u8> for Nag {
     }
 }
 
+/// Serialized as the number of the NAG.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Nag {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.0)
+    }
+}
+
 /// Error when parsing an invalid NAG.
 #[derive(Clone, Eq, PartialEq)]
 pub struct InvalidNag {
@@ -135,6 +150,190 @@ impl FromStr for Nag {
     }
 }
 
+/// A date from a `Date` header, like `2019.01.15`. Any component may be
+/// unknown, like in `2019.??.??`.
+///
+/// Dates are ordered chronologically, with unknown components before known
+/// ones.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Date {
+    /// The year, if known.
+    pub year: Option<u16>,
+    /// The month (1 to 12), if known.
+    pub month: Option<u8>,
+    /// The day of the month (1 to 31), if known.
+    pub day: Option<u8>,
+}
+
+impl Date {
+    /// Tries to parse a date from ASCII. Trailing components may be
+    /// omitted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::Date;
+    ///
+    /// let date = Date::from_ascii(b"2019.??.??")?;
+    /// assert_eq!((date.year, date.month, date.day), (Some(2019), None, None));
+    /// assert_eq!(Date::from_ascii(b"2019"), Ok(date));
+    /// # Ok::<_, pgn_reader::InvalidDate>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidDate`] error if the input is not of the form
+    /// `YYYY.MM.DD`, with `?` for unknown digits.
+    ///
+    /// [`InvalidDate`]: struct.InvalidDate.html
+    pub fn from_ascii(s: &[u8]) -> Result<Date, InvalidDate> {
+        fn component(
+            part: Option<&[u8]>,
+            len: usize,
+            max: u16,
+        ) -> Result<Option<u16>, InvalidDate> {
+            match part {
+                None => Ok(None),
+                Some(part) if part.len() != len => Err(InvalidDate { _priv: () }),
+                Some(part) if part.iter().all(|&ch| ch == b'?') => Ok(None),
+                Some(part) => match btoi::btou(part) {
+                    Ok(n) if (len == 4 || n >= 1) && n <= max => Ok(Some(n)),
+                    _ => Err(InvalidDate { _priv: () }),
+                },
+            }
+        }
+
+        let mut parts = s.split(|&ch| ch == b'.');
+        let date = Date {
+            year: component(parts.next(), 4, 9999)?,
+            month: component(parts.next(), 2, 12)?.map(|n| n as u8),
+            day: component(parts.next(), 2, 31)?.map(|n| n as u8),
+        };
+        if parts.next().is_some() {
+            return Err(InvalidDate { _priv: () });
+        }
+        Ok(date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.year {
+            Some(year) => write!(f, "{:04}", year)?,
+            None => f.write_str("????")?,
+        }
+        for part in [self.month, self.day] {
+            match part {
+                Some(n) => write!(f, ".{:02}", n)?,
+                None => f.write_str(".??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Date {
+    type Err = InvalidDate;
+
+    fn from_str(s: &str) -> Result<Date, InvalidDate> {
+        Date::from_ascii(s.as_bytes())
+    }
+}
+
+/// Error when parsing an invalid date.
+#[derive(Clone, Eq, PartialEq)]
+pub struct InvalidDate {
+    _priv: (),
+}
+
+impl fmt::Debug for InvalidDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InvalidDate").finish()
+    }
+}
+
+impl fmt::Display for InvalidDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "invalid date".fmt(f)
+    }
+}
+
+impl Error for InvalidDate {}
+
+/// An opening code from the Encyclopaedia of Chess Openings, like `B20`.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Eco {
+    /// The volume, `b'A'` to `b'E'`.
+    pub volume: u8,
+    /// The number within the volume, 0 to 99.
+    pub number: u8,
+}
+
+impl Eco {
+    /// Tries to parse an ECO code from ASCII.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::Eco;
+    ///
+    /// let sicilian = Eco::from_ascii(b"B20")?;
+    /// assert!(sicilian < Eco::from_ascii(b"C00")?);
+    /// assert_eq!(sicilian.to_string(), "B20");
+    /// # Ok::<_, pgn_reader::InvalidEco>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidEco`] error if the input is not a volume `A` to
+    /// `E` followed by two digits.
+    ///
+    /// [`InvalidEco`]: struct.InvalidEco.html
+    pub fn from_ascii(s: &[u8]) -> Result<Eco, InvalidEco> {
+        match *s {
+            [volume @ b'A'..=b'E', tens @ b'0'..=b'9', ones @ b'0'..=b'9'] => Ok(Eco {
+                volume,
+                number: (tens - b'0') * 10 + (ones - b'0'),
+            }),
+            _ => Err(InvalidEco { _priv: () }),
+        }
+    }
+}
+
+impl fmt::Display for Eco {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:02}", char::from(self.volume), self.number)
+    }
+}
+
+impl FromStr for Eco {
+    type Err = InvalidEco;
+
+    fn from_str(s: &str) -> Result<Eco, InvalidEco> {
+        Eco::from_ascii(s.as_bytes())
+    }
+}
+
+/// Error when parsing an invalid ECO code.
+#[derive(Clone, Eq, PartialEq)]
+pub struct InvalidEco {
+    _priv: (),
+}
+
+impl fmt::Debug for InvalidEco {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InvalidEco").finish()
+    }
+}
+
+impl fmt::Display for InvalidEco {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "invalid eco code".fmt(f)
+    }
+}
+
+impl Error for InvalidEco {}
+
 /// A header value.
 ///
 /// Provides helper methods for decoding [backslash
@@ -198,12 +397,32 @@ impl<'a> RawHeader<'a> {
     }
 }
 
+/// Appends `value` to `out`, escaping quotes and backslashes, so that the
+/// result can be written between the quotes of a header.
+pub(crate) fn escape_header_value(value: &[u8], out: &mut Vec<u8>) {
+    for &ch in value {
+        if ch == b'"' || ch == b'\\' {
+            out.push(b'\\');
+        }
+        out.push(ch);
+    }
+}
+
 impl<'a> fmt::Debug for RawHeader<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self.decode_utf8_lossy())
     }
 }
 
+/// Serialized as a string, decoded as with
+/// [`decode_utf8_lossy()`](#method.decode_utf8_lossy).
+#[cfg(feature = "serde")]
+impl<'a> serde::Serialize for RawHeader<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.decode_utf8_lossy())
+    }
+}
+
 /// A comment, excluding the braces.
 #[derive(Clone, Eq, PartialEq)]
 pub struct RawComment<'a>(pub &'a [u8]);
@@ -213,6 +432,77 @@ impl<'a> RawComment<'a> {
     pub fn as_bytes(&self) -> &[u8] {
         self.0
     }
+
+    /// Returns the comment without leading and trailing whitespace.
+    pub fn trim(&self) -> RawComment<'a> {
+        RawComment(self.0.trim_ascii())
+    }
+
+    /// Trims the comment and collapses each run of whitespace, including the
+    /// hard line breaks that export format inserts into long comments, into
+    /// a single space. Allocates only when the comment actually contains
+    /// such whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::RawComment;
+    ///
+    /// let comment = RawComment(b" White resigns\n after   a long\r\n think ");
+    /// assert_eq!(comment.decode().as_ref(), b"White resigns after a long think");
+    /// ```
+    pub fn decode(&self) -> Cow<'a, [u8]> {
+        let trimmed = self.0.trim_ascii();
+        let mut head = 0;
+        let mut decoded: Vec<u8> = Vec::new();
+        let mut i = 0;
+        while i < trimmed.len() {
+            if trimmed[i].is_ascii_whitespace() {
+                let end = i + trimmed[i..]
+                    .iter()
+                    .take_while(|ch| ch.is_ascii_whitespace())
+                    .count();
+                if end - i > 1 || trimmed[i] != b' ' {
+                    decoded.extend_from_slice(&trimmed[head..i]);
+                    decoded.push(b' ');
+                    head = end;
+                }
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+        if head == 0 {
+            Cow::Borrowed(trimmed)
+        } else {
+            decoded.extend_from_slice(&trimmed[head..]);
+            Cow::Owned(decoded)
+        }
+    }
+
+    /// Tries to decode the comment as UTF-8, with whitespace normalized as
+    /// in [`decode()`](#method.decode). This is guaranteed to succeed on
+    /// valid PGNs.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the comment contains an invalid UTF-8 byte sequence.
+    pub fn decode_utf8(&self) -> Result<Cow<'a, str>, Utf8Error> {
+        Ok(match self.decode() {
+            Cow::Borrowed(borrowed) => Cow::Borrowed(str::from_utf8(borrowed)?),
+            Cow::Owned(owned) => Cow::Owned(String::from_utf8(owned).map_err(|e| e.utf8_error())?),
+        })
+    }
+
+    /// Decodes the comment as UTF-8, with whitespace normalized as in
+    /// [`decode()`](#method.decode), replacing any invalid byte sequences
+    /// with the placeholder � U+FFFD.
+    pub fn decode_utf8_lossy(&self) -> Cow<'a, str> {
+        match self.decode() {
+            Cow::Borrowed(borrowed) => String::from_utf8_lossy(borrowed),
+            Cow::Owned(owned) => Cow::Owned(String::from_utf8_lossy(&owned).into_owned()),
+        }
+    }
 }
 
 impl<'a> fmt::Debug for RawComment<'a> {
@@ -221,6 +511,15 @@ impl<'a> fmt::Debug for RawComment<'a> {
     }
 }
 
+/// Serialized as a string, decoded as with
+/// [`decode_utf8_lossy()`](#method.decode_utf8_lossy).
+#[cfg(feature = "serde")]
+impl<'a> serde::Serialize for RawComment<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.decode_utf8_lossy())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,4 +540,20 @@ mod tests {
         let header = RawHeader(b"\\Hello \\\"world\\\\");
         assert_eq!(header.decode().as_ref(), b"\\Hello \"world\\");
     }
+
+    #[test]
+    fn test_raw_comment() {
+        let comment = RawComment(b"Hello world");
+        assert!(matches!(comment.decode(), Cow::Borrowed(b"Hello world")));
+
+        let comment = RawComment(b"  Hello world\n");
+        assert!(matches!(comment.decode(), Cow::Borrowed(b"Hello world")));
+
+        let comment = RawComment(b"Hello\r\nworld \t and  more");
+        assert_eq!(comment.decode().as_ref(), b"Hello world and more");
+
+        let comment = RawComment(b" \n ");
+        assert_eq!(comment.decode().as_ref(), b"");
+        assert_eq!(comment.trim(), RawComment(b""));
+    }
 }
@@ -25,6 +25,11 @@ pub trait Visitor {
     /// Value produced by the visitor after reading a game.
     type Result;
 
+    /// Called by readers over multiple sources, like the entries of an
+    /// archive, before the games of each source. Not called by
+    /// [`BufferedReader`](struct.BufferedReader.html) itself.
+    fn begin_source(&mut self, _name: &str) {}
+
     /// Called at the start of the game.
     fn begin_game(&mut self) {}
 
@@ -41,19 +46,36 @@ pub trait Visitor {
 
     /// Called for each move, like `Nf3+`.
     fn san(&mut self, _san_plus: SanPlus) {}
+    /// Called for each move, like [`san()`](trait.Visitor.html#method.san),
+    /// but also receives the `ply` of the move within the current line,
+    /// starting at 0 for the first move of the game. Variations restart at
+    /// the ply of the move they replace.
+    ///
+    /// Even plies are moves of the side to move at the start of the game
+    /// (White, unless a `FEN` header says otherwise).
+    ///
+    /// The default implementation calls
+    /// [`san()`](trait.Visitor.html#method.san).
+    fn san_at(&mut self, _ply: usize, san_plus: SanPlus) {
+        self.san(san_plus);
+    }
     /// Called for each numeric annotation glyph like `!?` or `$7`.
     fn nag(&mut self, _nag: Nag) {}
     /// Called for each `{ comment }`.
     fn comment(&mut self, _comment: RawComment<'_>) {}
-    /// Called for each `(`. May skip over the following variation directly
-    /// to [`end_variation()`](trait.Visitor.html#method.end_variation) (or to
-    /// [`end_game()`](trait.Visitor.html#tymethod.end_game) if no matching `)`
-    /// follows before the end of the game.
-    fn begin_variation(&mut self) -> Skip {
+    /// Called for each `(`, with the nesting `depth` of the new variation
+    /// (1 for variations of the mainline) and the `ply` of the move it
+    /// replaces (0 for alternatives to the first move).
+    ///
+    /// May skip over the following variation directly to
+    /// [`end_variation()`](trait.Visitor.html#method.end_variation).
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
         Skip(false)
     }
-    /// Called for each `)`. It is *not* guaranteed that there was a
-    /// matching `(`.
+    /// Called for each `)`. Calls are balanced with
+    /// [`begin_variation()`](trait.Visitor.html#method.begin_variation):
+    /// unmatched `)` are ignored, and missing `)` are synthesized at the end
+    /// of the game.
     fn end_variation(&mut self) {}
     /// Called for each game termination, like `*` or `1-0`.
     fn outcome(&mut self, _outcome: Option<Outcome>) {}
@@ -70,7 +92,7 @@ impl Visitor for SkipVisitor {
     fn end_headers(&mut self) -> Skip {
         Skip(true)
     }
-    fn begin_variation(&mut self) -> Skip {
+    fn begin_variation(&mut self, _depth: usize, _ply: usize) -> Skip {
         Skip(true)
     }
     fn end_game(&mut self) {}